@@ -0,0 +1,223 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use checksums::{hash_file, Algorithm};
+use internet_archive::metadata::Metadata;
+use minijinja::context;
+use serde::Serialize;
+
+use crate::{
+  conf::{Bios, BiosFile, BiosSource},
+  frontend::link_or_copy,
+  ia_cache,
+  package::{read_pkgver, render_template},
+};
+
+/// Verification result of one expected BIOS file.
+pub enum BiosStatus {
+  Ok,
+  Missing,
+  /// `(algorithm, expected, found)`.
+  Mismatch(&'static str, String, String),
+}
+
+pub struct BiosCheck {
+  pub path: String,
+  pub status: BiosStatus,
+}
+
+/// Outcome of `--bios`.
+pub struct BiosReport {
+  /// Package directory (`<package>/`).
+  pub directory: PathBuf,
+  pub pkgver: u32,
+  pub checks: Vec<BiosCheck>,
+}
+
+impl BiosReport {
+  /// True when every expected file was found with the expected hashes.
+  pub fn complete(&self) -> bool {
+    self
+      .checks
+      .iter()
+      .all(|c| matches!(c.status, BiosStatus::Ok))
+  }
+}
+
+/// A verified file, as listed in the PKGBUILD.
+#[derive(Serialize)]
+struct PackagedFile {
+  /// Filename in the package directory.
+  name: String,
+  /// `source=` entry (`name::url` for Internet Archive files).
+  source: String,
+  sha1: String,
+  /// Destination, relative to `/userdata/bios`.
+  path: String,
+}
+
+/// Flat filename of a BIOS in the package directory: makepkg sources cannot
+/// contain `/`, so `dc/dc_boot.bin` becomes `dc_dc_boot.bin`.
+fn package_name(path: &str) -> String {
+  path.trim_start_matches('/').replace('/', "_")
+}
+
+fn basename(path: &str) -> &str {
+  path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Compares the hashes found with the ones expected; a file listed without
+/// any hash only needs to be present.
+fn verify(expected: &BiosFile, md5: Option<&str>, sha1: Option<&str>) -> BiosStatus {
+  let pairs = [("md5", &expected.md5, md5), ("sha1", &expected.sha1, sha1)];
+  for (algorithm, want, found) in pairs {
+    let Some(want) = want else { continue };
+    let found = found.unwrap_or("").to_lowercase();
+    if want.to_lowercase() != found {
+      return BiosStatus::Mismatch(algorithm, want.to_lowercase(), found);
+    }
+  }
+  BiosStatus::Ok
+}
+
+/// Where the BIOS files are looked up.
+enum Origin {
+  Folder(PathBuf),
+  Archive(Metadata),
+}
+
+/// Looks `file` up in a folder source, at its `/userdata/bios` path first,
+/// then by filename at the root.
+fn folder_file(root: &Path, file: &BiosFile) -> Option<PathBuf> {
+  [root.join(&file.path), root.join(basename(&file.path))]
+    .into_iter()
+    .find(|p| p.is_file())
+}
+
+/// Checks an Internet Archive file against the item metadata hashes.
+fn archive_file(metadata: &Metadata, file: &BiosFile) -> (BiosStatus, Option<PackagedFile>) {
+  let found = metadata
+    .files
+    .iter()
+    .find(|f| f.name == file.path)
+    .or_else(|| {
+      metadata
+        .files
+        .iter()
+        .find(|f| basename(&f.name) == basename(&file.path))
+    });
+  let Some(f) = found else {
+    return (BiosStatus::Missing, None);
+  };
+  let name = package_name(&file.path);
+  let url = metadata
+    .file_urls(&f.name)
+    .ok()
+    .and_then(|urls| urls.into_iter().next())
+    .unwrap_or_default();
+  let packaged = PackagedFile {
+    source: format!("{}::{}", name, url),
+    name,
+    sha1: f.sha1.clone().unwrap_or_else(|| "SKIP".to_string()),
+    path: file.path.clone(),
+  };
+  (
+    verify(file, f.md5.as_deref(), f.sha1.as_deref()),
+    Some(packaged),
+  )
+}
+
+/// Hashes a folder file and, when it verifies, places it in the package
+/// directory.
+fn local_file(
+  root: &Path,
+  file: &BiosFile,
+  directory: &Path,
+) -> io::Result<(BiosStatus, Option<PackagedFile>)> {
+  let Some(local) = folder_file(root, file) else {
+    return Ok((BiosStatus::Missing, None));
+  };
+  let name = package_name(&file.path);
+  let md5 = hash_file(&local, Algorithm::MD5).to_lowercase();
+  let sha1 = hash_file(&local, Algorithm::SHA1).to_lowercase();
+  let status = verify(file, Some(&md5), Some(&sha1));
+  if matches!(status, BiosStatus::Ok) {
+    link_or_copy(&local, &directory.join(&name))?;
+  }
+  let packaged = PackagedFile {
+    source: name.clone(),
+    name,
+    sha1,
+    path: file.path.clone(),
+  };
+  Ok((status, Some(packaged)))
+}
+
+/// Verifies the BIOS files of a system and writes `<package>/PKGBUILD`, which
+/// installs the verified ones into `/userdata/bios`.
+///
+/// Folder files are hashed and placed in the package directory; Internet
+/// Archive files are checked against the item metadata (see `ia_cache`) and downloaded by
+/// makepkg. Missing or mismatching files are reported and left out of the
+/// package. `pkgver` is bumped only when the PKGBUILD content changes.
+pub fn build_package(system: &str, bios: &Bios, refresh: bool) -> io::Result<BiosReport> {
+  let directory = PathBuf::from(&bios.package);
+  fs::create_dir_all(&directory)?;
+
+  let origin = match &bios.source {
+    BiosSource::Folder(path) => Origin::Folder(PathBuf::from(path)),
+    BiosSource::InternetArchive(item) => {
+      let loaded = ia_cache::load(item, refresh)?;
+      if let Some(e) = loaded.fallback {
+        eprintln!("Warning: using cached metadata of {}: {}", item, e);
+      }
+      Origin::Archive(loaded.metadata)
+    }
+  };
+
+  let mut checks = Vec::new();
+  let mut files = Vec::new();
+  for file in &bios.files {
+    let (status, packaged) = match &origin {
+      Origin::Folder(root) => local_file(root, file, &directory)?,
+      Origin::Archive(metadata) => archive_file(metadata, file),
+    };
+    if matches!(status, BiosStatus::Ok) {
+      files.extend(packaged);
+    }
+    checks.push(BiosCheck {
+      path: file.path.clone(),
+      status,
+    });
+  }
+
+  let render = |pkgver: u32| {
+    let ctx = context! {
+      pkgname => bios.package,
+      pkgver => pkgver,
+      system => system,
+      files => files,
+    };
+    render_template(
+      include_str!("../assets/templates/pkgbuild/bios.jinja"),
+      &ctx,
+    )
+  };
+  let pkgbuild_path = directory.join("PKGBUILD");
+  let previous = read_pkgver(&directory);
+  let existing = fs::read_to_string(&pkgbuild_path).ok();
+  let pkgver = if previous > 0 && existing.as_deref() == Some(render(previous).as_str()) {
+    previous
+  } else {
+    previous + 1
+  };
+  fs::write(&pkgbuild_path, render(pkgver))?;
+
+  Ok(BiosReport {
+    directory,
+    pkgver,
+    checks,
+  })
+}
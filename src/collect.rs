@@ -0,0 +1,206 @@
+use std::{
+  io,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use internet_archive::metadata::Metadata;
+use snafu::{OptionExt, ResultExt, Snafu};
+
+use crate::{
+  conf::{FolderSource, HttpSource, IaItem, Source, System},
+  folder::{self, Skipped},
+  http, ia_cache,
+  rom::{IaSource, RomSource, RomSourceData},
+  ui::Ui,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+  #[snafu(display("Could not fetch metadata of {}: {}", item, source))]
+  FetchMetadata { source: io::Error, item: String },
+  #[snafu(display("Could not scan {}: {}", path, source))]
+  ScanFolder { source: io::Error, path: String },
+  #[snafu(display("Could not list {}: {}", location, source))]
+  ListHttp { source: io::Error, location: String },
+}
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A file of a source left out on its own. Unlike an `Error`, it is left out
+/// the same way on every run.
+#[derive(Debug, Snafu)]
+pub enum Skip {
+  #[snafu(display("No download URL for {} in {}: {}", file, item, message))]
+  FileUrl {
+    item: String,
+    file: String,
+    message: String,
+  },
+  #[snafu(display("Unusable file name {} in {}", name, origin))]
+  BadFilename { name: String, origin: String },
+  #[snafu(display(
+    "{} skipped in {}: same file name as {}",
+    path.display(),
+    origin,
+    kept.display()
+  ))]
+  SameName {
+    path: PathBuf,
+    kept: PathBuf,
+    origin: String,
+  },
+}
+
+/// ROMs collected from the sources of a system, and what went wrong.
+#[derive(Default)]
+pub struct Collected {
+  pub sources: Vec<RomSourceData>,
+  /// HTTP files left out for lack of a known sha1.
+  pub unhashed: Vec<String>,
+  /// `(item, network error)` of IA items read from the metadata cache.
+  pub stale_metadata: Vec<(String, String)>,
+  /// Sources that could not be collected.
+  pub errors: Vec<Error>,
+  /// Files left out of their source.
+  pub skipped: Vec<Skip>,
+  /// Origins of the sources that failed as a whole: their ROMs are missing
+  /// from `sources` but must not be treated as gone.
+  pub failed_origins: Vec<String>,
+}
+
+impl Collected {
+  fn record(&mut self, origin: String, result: Result<()>) {
+    if let Err(e) = result {
+      self.errors.push(e);
+      self.failed_origins.push(origin);
+    }
+  }
+}
+
+/// `(filename, download URL)` of a file of an IA item.
+fn ia_file(item: &IaItem, metadata: &Metadata, name: &str) -> Result<(String, String), Skip> {
+  let filename = Path::new(name)
+    .file_name()
+    .and_then(|n| n.to_str())
+    .context(BadFilenameSnafu {
+      name,
+      origin: item.origin(),
+    })?
+    .to_string();
+  let rom_url = metadata
+    .file_urls(name)
+    .map_err(|e| Skip::FileUrl {
+      item: item.item.clone(),
+      file: name.to_string(),
+      message: e.to_string(),
+    })?
+    .into_iter()
+    .next()
+    .unwrap_or_default();
+  Ok((filename, rom_url))
+}
+
+fn collect_ia(item: &IaItem, refresh_metadata: bool, out: &mut Collected) -> Result<()> {
+  let loaded = ia_cache::load(&item.item, refresh_metadata)
+    .context(FetchMetadataSnafu { item: &item.item })?;
+  if let Some(e) = loaded.fallback {
+    out.stale_metadata.push((item.item.clone(), e));
+  }
+  let metadata = Arc::new(loaded.metadata);
+  for file in metadata
+    .files
+    .iter()
+    .filter(|f| item.matcher.matches(&f.name))
+  {
+    // A file with an unusable name or URL is skipped alone.
+    let (filename, rom_url) = match ia_file(item, &metadata, &file.name) {
+      Ok(found) => found,
+      Err(e) => {
+        out.skipped.push(e);
+        continue;
+      }
+    };
+    out.sources.push(RomSourceData {
+      file_name: file.name.clone(),
+      filename,
+      source: RomSource::InternetArchive(IaSource {
+        rom_url,
+        crc32: file.crc32.clone(),
+        md5: file.md5.clone(),
+        sha1: file.sha1.clone(),
+        size: file
+          .size
+          .as_deref()
+          .and_then(|s| s.parse().ok())
+          .unwrap_or(0),
+        metadata: Arc::clone(&metadata),
+      }),
+      extra_discs: Vec::new(),
+      romname: String::new(),
+      game_dir: None,
+      origin: item.origin(),
+    });
+  }
+  Ok(())
+}
+
+fn collect_folder(source: &FolderSource, out: &mut Collected) -> Result<()> {
+  let (found, skipped) = folder::collect(source).context(ScanFolderSnafu { path: &source.path })?;
+  out.sources.extend(found);
+  out
+    .skipped
+    .extend(skipped.into_iter().map(|skipped| match skipped {
+      Skipped::NonUtf8(path) => Skip::BadFilename {
+        name: path.to_string_lossy().into_owned(),
+        origin: source.origin(),
+      },
+      Skipped::SameName { path, kept } => Skip::SameName {
+        path,
+        kept,
+        origin: source.origin(),
+      },
+    }));
+  Ok(())
+}
+
+fn collect_http(source: &HttpSource, out: &mut Collected) -> Result<()> {
+  let (found, unhashed) = http::collect(source).context(ListHttpSnafu {
+    location: source.location(),
+  })?;
+  out.sources.extend(found);
+  out.unhashed.extend(unhashed);
+  Ok(())
+}
+
+/// Collects the ROMs of every source of `system`, in priority order.
+///
+/// A source that cannot be read (unreachable item, missing folder…) is
+/// recorded in `Collected::errors` and `failed_origins`, and collection goes
+/// on with the next one. A file that cannot be collected (unusable name or
+/// URL, name taken…) is recorded in `Collected::skipped` instead.
+pub fn collect(system: &System, refresh_metadata: bool, ui: &Ui) -> Collected {
+  let mut out = Collected::default();
+  for source in &system.sources {
+    match source {
+      Source::InternetArchive(items) => {
+        for item in items {
+          ui.fetching_metadata(&item.item);
+          let result = collect_ia(item, refresh_metadata, &mut out);
+          out.record(item.origin(), result);
+        }
+      }
+      Source::Folder(folder) => {
+        ui.fetching_metadata(&folder.path);
+        let result = collect_folder(folder, &mut out);
+        out.record(folder.origin(), result);
+      }
+      Source::Http(http) => {
+        ui.fetching_metadata(http.location());
+        let result = collect_http(http, &mut out);
+        out.record(http.origin(), result);
+      }
+    }
+  }
+  out
+}
@@ -0,0 +1,88 @@
+use glob::Pattern;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+/// Syntaxe des motifs `filter` / `exclude` d'une source.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternSyntax {
+  #[default]
+  Glob,
+  Regex,
+}
+
+/// Partie du chemin comparée aux motifs.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchTarget {
+  /// Nom du fichier seul.
+  #[default]
+  Basename,
+  /// Chemin dans l'item IA, ou relatif au dossier source.
+  Path,
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+  Glob(Pattern),
+  Regex(Regex),
+}
+
+impl Matcher {
+  fn new(pattern: &str, syntax: PatternSyntax) -> Result<Matcher, String> {
+    match syntax {
+      PatternSyntax::Glob => Pattern::new(pattern)
+        .map(Matcher::Glob)
+        .map_err(|e| format!("{}: {}", pattern, e)),
+      PatternSyntax::Regex => Regex::new(pattern)
+        .map(Matcher::Regex)
+        .map_err(|e| format!("{}: {}", pattern, e)),
+    }
+  }
+
+  fn matches(&self, s: &str) -> bool {
+    match self {
+      Matcher::Glob(p) => p.matches(s),
+      Matcher::Regex(r) => r.is_match(s),
+    }
+  }
+}
+
+/// Motifs compilés d'une source : un fichier est retenu s'il correspond à un
+/// motif `filter` et à aucun motif `exclude`. Vide, ne retient rien.
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+  include: Vec<Matcher>,
+  exclude: Vec<Matcher>,
+  target: MatchTarget,
+}
+
+impl FileFilter {
+  pub fn new(
+    include: &[String],
+    exclude: &[String],
+    syntax: PatternSyntax,
+    target: MatchTarget,
+  ) -> Result<FileFilter, String> {
+    let compile = |patterns: &[String]| {
+      patterns
+        .iter()
+        .map(|p| Matcher::new(p, syntax))
+        .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(FileFilter {
+      include: compile(include)?,
+      exclude: compile(exclude)?,
+      target,
+    })
+  }
+
+  /// `path` : chemin dans l'item IA, ou relatif au dossier source.
+  pub fn matches(&self, path: &str) -> bool {
+    let s = match self.target {
+      MatchTarget::Basename => path.rsplit('/').next().unwrap_or(path),
+      MatchTarget::Path => path,
+    };
+    self.include.iter().any(|m| m.matches(s)) && !self.exclude.iter().any(|m| m.matches(s))
+  }
+}
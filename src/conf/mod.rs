@@ -1,9 +1,16 @@
+mod filter;
 mod update;
 
+pub use filter::{FileFilter, MatchTarget, PatternSyntax};
+
 use serde_derive::Deserialize;
-use std::{fs, io, path::PathBuf};
+use std::{
+  collections::BTreeMap,
+  fs, io,
+  path::{Path, PathBuf},
+};
 
-use snafu::{Backtrace, ResultExt, Snafu};
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
 
 // Source: https://www.screenscraper.fr — langues supportées pour les synopsis
 pub const SUPPORTED_LANGS: &[(&str, &str)] = &[
@@ -40,6 +47,23 @@ struct ItemOld {
 pub struct IaItem {
   pub item: String,
   pub filter: Vec<String>,
+  /// Motifs écartant des fichiers retenus par `filter`.
+  #[serde(default)]
+  pub exclude: Vec<String>,
+  #[serde(default)]
+  pub syntax: PatternSyntax,
+  #[serde(default)]
+  pub match_on: MatchTarget,
+  /// `filter` et `exclude` compilés au chargement.
+  #[serde(skip)]
+  pub matcher: FileFilter,
+}
+
+impl IaItem {
+  /// Origine enregistrée dans l'état pour les ROMs de cet item.
+  pub fn origin(&self) -> String {
+    format!("internet_archive:{}", self.item)
+  }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -47,6 +71,379 @@ pub struct IaItem {
 pub struct FolderSource {
   pub path: String,
   pub filter: Vec<String>,
+  #[serde(default)]
+  pub exclude: Vec<String>,
+  #[serde(default)]
+  pub syntax: PatternSyntax,
+  #[serde(default)]
+  pub match_on: MatchTarget,
+  #[serde(skip)]
+  pub matcher: FileFilter,
+  /// Parcourt aussi les sous-dossiers.
+  #[serde(default)]
+  pub recursive: bool,
+  /// Profondeur maximale des sous-dossiers parcourus (1 = sous-dossiers
+  /// directs). Absent : illimitée.
+  #[serde(default)]
+  pub max_depth: Option<usize>,
+  /// Suit les liens symboliques vers des dossiers (les liens vers des
+  /// fichiers sont toujours suivis).
+  #[serde(default)]
+  pub follow_symlinks: bool,
+  /// Profondeur à laquelle chaque sous-dossier est un jeu : tous ses fichiers
+  /// forment une seule ROM, installée dans un dossier du même nom.
+  #[serde(default)]
+  pub game_dir_depth: Option<usize>,
+  /// URL de base d'un miroir servant les ROMs du dossier. Absent : les
+  /// PKGBUILDs référencent les fichiers copiés dans le répertoire du paquet.
+  #[serde(default)]
+  pub mirror: Option<String>,
+}
+
+impl FolderSource {
+  /// Origine enregistrée dans l'état pour les ROMs de ce dossier.
+  pub fn origin(&self) -> String {
+    format!("folder:{}", self.path)
+  }
+}
+
+/// Serveur HTTP : page d'index (autoindex nginx, Apache…) ou manifeste.
+#[derive(Deserialize, Clone, Debug)]
+pub struct HttpSource {
+  /// Page d'index listant les fichiers.
+  #[serde(default)]
+  pub url: Option<String>,
+  /// Manifeste YAML ou JSON (`name`, `size`, `sha1`, `url`), à la place de
+  /// la page d'index.
+  #[serde(default)]
+  pub manifest: Option<String>,
+  /// Fichier au format `sha1sum` donnant les sha1 des fichiers de l'index,
+  /// relatif à `url`. Les fichiers sans sha1 connu sont ignorés.
+  #[serde(default)]
+  pub sha1sums: Option<String>,
+  pub filter: Vec<String>,
+  #[serde(default)]
+  pub exclude: Vec<String>,
+  #[serde(default)]
+  pub syntax: PatternSyntax,
+  #[serde(default)]
+  pub match_on: MatchTarget,
+  #[serde(skip)]
+  pub matcher: FileFilter,
+}
+
+impl HttpSource {
+  /// URL de l'index ou du manifeste.
+  pub fn location(&self) -> &str {
+    self
+      .url
+      .as_deref()
+      .or(self.manifest.as_deref())
+      .unwrap_or("")
+  }
+
+  /// Origine enregistrée dans l'état pour les ROMs de ce serveur.
+  pub fn origin(&self) -> String {
+    format!("http:{}", self.location())
+  }
+}
+
+/// Stratégie de désambiguïsation quand deux ROMs produisent le même nom de paquet.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionStrategy {
+  /// Suffixe numérique (`-2`, `-3`, …) attribué dans l'ordre des noms de fichiers.
+  #[default]
+  #[serde(rename = "numeric")]
+  Numeric,
+  /// Suffixe des 8 premiers caractères du sha1 de la ROM (repli numérique si inconnu).
+  #[serde(rename = "sha1")]
+  Sha1,
+}
+
+/// Choix entre plusieurs sources fournissant la même ROM.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+  /// Même nom de fichier : la première source listée l'emporte.
+  #[default]
+  Order,
+  /// Même contenu (sha1), quel que soit le nom : seule la première copie est
+  /// gardée. Les noms identiques au contenu différent suivent l'ordre.
+  Sha1,
+  /// Même nom de fichier : la copie vérifiée par le DAT l'emporte, puis l'ordre.
+  Dat,
+}
+
+/// Origine des URLs de médias écrites dans les PKGBUILDs.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MediaSource {
+  /// URLs ScreenScraper reconstruites à partir du système, du jeu et du média.
+  #[default]
+  #[serde(rename = "screenscraper")]
+  ScreenScraper,
+  /// URL exacte renvoyée par ScreenScraper (`Media.url`), sans les
+  /// identifiants du compte (`devid`, `ssid`, `sspassword`…).
+  #[serde(rename = "ss_url")]
+  SsUrl,
+  /// URL exacte, identifiants compris : ScreenScraper peut refuser de servir
+  /// le fichier sans eux. Réservé aux PKGBUILDs qui restent privés.
+  #[serde(rename = "ss_url_with_credentials")]
+  SsUrlWithCredentials,
+  /// Miroir des fichiers déjà téléchargés par rompom :
+  /// `<base>/<répertoire du paquet>/<fichier média>`.
+  #[serde(rename = "mirror")]
+  Mirror(String),
+}
+
+/// Modèles de lanceurs intégrés, utilisables par leur nom dans `launcher.template`.
+const BUILTIN_LAUNCHERS: &[(&str, &str)] = &[
+  (
+    "openbor",
+    include_str!("../../assets/templates/launcher/openbor.jinja"),
+  ),
+  (
+    "scummvm",
+    include_str!("../../assets/templates/launcher/scummvm.jinja"),
+  ),
+];
+
+/// Lanceur généré pour chaque jeu d'un système de type « port » (OpenBOR,
+/// ScummVM, DOSBox, WADs Doom…). Le lanceur est installé à côté de la ROM et
+/// sert de chemin de jeu dans `gamelist.xml`.
+///
+/// Les modèles reçoivent `rom` (échappé pour des guillemets simples), `name`,
+/// `romname`, `stem` (nom de la ROM sans extension), `dir` et `args`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Launcher {
+  /// Nom d'un modèle intégré (`openbor`, `scummvm`) ou chemin d'un fichier
+  /// jinja, relatif au répertoire de configuration.
+  pub template: String,
+  /// Nom du fichier lanceur (modèle jinja), ex. `{{ name }}.sh`.
+  pub filename: String,
+  /// Chemin du jeu dans `gamelist.xml` (modèle jinja, reçoit aussi
+  /// `launcher`). Absent : `./{{ launcher }}`.
+  #[serde(default)]
+  pub path: Option<String>,
+  /// Arguments propres à chaque jeu, indexés par nom de fichier ROM.
+  #[serde(default)]
+  pub args: BTreeMap<String, String>,
+  /// Contenu du modèle, chargé par `Conf::load`.
+  #[serde(skip)]
+  pub source: String,
+}
+
+impl Launcher {
+  /// Lanceur historique d'OpenBOR (système 214), utilisé sans configuration.
+  fn openbor() -> Launcher {
+    Launcher {
+      template: "openbor".to_string(),
+      filename: "{{ name }}.sh".to_string(),
+      path: None,
+      args: BTreeMap::new(),
+      source: String::new(),
+    }
+  }
+
+  /// Charge le modèle et vérifie la syntaxe des trois modèles jinja.
+  fn load(mut self, confdir: &Path, system: &str) -> Result<Launcher> {
+    self.source = match BUILTIN_LAUNCHERS.iter().find(|(n, _)| *n == self.template) {
+      Some((_, src)) => src.to_string(),
+      None => {
+        let path = confdir.join(&self.template);
+        fs::read_to_string(&path).context(ReadLauncherSnafu { path })?
+      }
+    };
+    let mut env = minijinja::Environment::new();
+    for (name, src) in [
+      ("template", self.source.as_str()),
+      ("filename", self.filename.as_str()),
+      ("path", self.path.as_deref().unwrap_or("")),
+    ] {
+      env
+        .add_template(name, src)
+        .context(InvalidLauncherSnafu { system })?;
+    }
+    Ok(self)
+  }
+}
+
+/// Réglages Batocera propres à certains jeux (core, émulateur, ratio…),
+/// livrés dans le paquet et fusionnés dans `batocera.conf` à l'installation.
+///
+/// Une règle s'applique à une ROM si son nom de fichier correspond au glob
+/// `match` ou si son identifiant de jeu ScreenScraper vaut `game_id`. Quand
+/// plusieurs règles s'appliquent, la dernière l'emporte pour une même clé.
+#[derive(Deserialize, Clone, Debug)]
+pub struct GameConfigRule {
+  #[serde(default, rename = "match")]
+  pub pattern: Option<String>,
+  #[serde(default)]
+  pub game_id: Option<u32>,
+  /// Clés `batocera.conf` sans préfixe (`core`, `emulator`, `ratio`…).
+  pub settings: BTreeMap<String, String>,
+}
+
+impl GameConfigRule {
+  /// Vérifie la syntaxe du glob `match`.
+  fn check(&self, system: &str, index: usize) -> Result<()> {
+    match self.pattern.as_deref().map(glob::Pattern::new) {
+      Some(Err(e)) => Err(Error::InvalidGameConfig {
+        system: system.to_string(),
+        rule: index + 1,
+        message: format!("{}: {}", self.pattern.as_deref().unwrap_or(""), e),
+      }),
+      _ => Ok(()),
+    }
+  }
+}
+
+/// Catégorie de variantes écartées par la sélection 1G1R, d'après les tags
+/// No-Intro du nom de fichier.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Exclusion {
+  /// `(Beta)`, `(Beta 2)`…
+  Beta,
+  /// `(Proto)`, `(Proto 1)`…
+  Proto,
+  /// `(Demo)`, `(Sample)`, `(Trial)`.
+  Demo,
+  /// `(Unl)`.
+  Unlicensed,
+  /// `(Pirate)`.
+  Pirate,
+  /// `(Aftermarket)`, `(Homebrew)`.
+  Aftermarket,
+  /// `(Kiosk)`.
+  Kiosk,
+}
+
+fn default_exclusions() -> Vec<Exclusion> {
+  vec![
+    Exclusion::Beta,
+    Exclusion::Proto,
+    Exclusion::Demo,
+    Exclusion::Unlicensed,
+    Exclusion::Pirate,
+  ]
+}
+
+/// Sélection « un jeu, une ROM » parmi les variantes d'un même jeu.
+///
+/// Les variantes sont regroupées par parent DAT (`cloneof`) ou, à défaut,
+/// par titre ; la meilleure selon `regions` puis `languages` puis la révision
+/// est conservée.
+#[derive(Deserialize, Clone, Debug)]
+pub struct OneGameOneRom {
+  /// Régions No-Intro par ordre de préférence (`Europe`, `USA`, `World`…).
+  pub regions: Vec<String>,
+  /// Langues par ordre de préférence (`Fr`, `En`…).
+  #[serde(default)]
+  pub languages: Vec<String>,
+  #[serde(default = "default_exclusions")]
+  pub exclude: Vec<Exclusion>,
+}
+
+/// Origine des fichiers BIOS d'un système.
+#[derive(Deserialize, Clone, Debug)]
+pub enum BiosSource {
+  /// Dossier local, à plat ou reproduisant l'arborescence de `/userdata/bios`.
+  #[serde(rename = "folder")]
+  Folder(String),
+  /// Item Internet Archive.
+  #[serde(rename = "internet_archive")]
+  InternetArchive(String),
+}
+
+/// Fichier BIOS attendu, tel que listé par Batocera (`batocera-systems`).
+#[derive(Deserialize, Clone, Debug)]
+pub struct BiosFile {
+  /// Chemin relatif à `/userdata/bios` (ex. `dc/dc_boot.bin`).
+  pub path: String,
+  #[serde(default)]
+  pub md5: Option<String>,
+  #[serde(default)]
+  pub sha1: Option<String>,
+}
+
+/// Paquet BIOS du système (`bios-*`), généré par `--bios`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Bios {
+  /// Nom du paquet ; par défaut le `depends` du système.
+  #[serde(default)]
+  pub package: String,
+  #[serde(with = "serde_yaml::with::singleton_map")]
+  pub source: BiosSource,
+  pub files: Vec<BiosFile>,
+}
+
+impl Bios {
+  /// Complète le nom du paquet à partir du `depends` du système.
+  fn load(mut self, depends: Option<&str>, system: &str) -> Result<Bios> {
+    if self.package.is_empty() {
+      self.package = depends
+        .map(str::to_string)
+        .context(MissingBiosPackageSnafu { system })?;
+    }
+    Ok(self)
+  }
+}
+
+/// Champ optionnel de `description.xml`, activé par système via `extra_fields`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GameField {
+  /// MD5 de la ROM.
+  Md5,
+  /// CRC32 de la ROM (`hash`), utilisé pour le netplay.
+  Hash,
+  /// Langues de la ROM (`en,fr`).
+  Lang,
+  /// Famille de jeux ScreenScraper.
+  Family,
+  /// Identifiant ScreenScraper du genre principal.
+  GenreId,
+  /// Jeu adapté aux enfants, déduit des classifications d'âge.
+  KidGame,
+  /// Système arcade d'origine (`arcadesystemname`). Accepté mais laissé vide :
+  /// ScreenScraper ne fournit que la plateforme du jeu, pas la carte arcade.
+  ArcadeSystemName,
+}
+
+/// Export Pegasus (`metadata.pegasus.txt`).
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct PegasusExport {
+  /// Commande `launch:` de la collection (ex. `retroarch -L … "{file.path}"`).
+  #[serde(default)]
+  pub launch: Option<String>,
+}
+
+/// Export RetroArch (playlist `.lpl`).
+#[derive(Deserialize, Clone, Debug)]
+pub struct RetroArchExport {
+  /// Nom de la playlist, idéalement celui de la base RetroArch du système
+  /// (ex. `Nintendo - Super Nintendo Entertainment System`).
+  pub playlist: String,
+  /// Répertoire des ROMs sur la machine cible, préfixé aux chemins des jeux.
+  pub rom_path: String,
+  /// Core par défaut. Absent : `DETECT`.
+  #[serde(default)]
+  pub core_path: Option<String>,
+  #[serde(default)]
+  pub core_name: Option<String>,
+}
+
+/// Frontends pour lesquels exporter les métadonnées en fin de run, dans
+/// `<système>.export/<frontend>/`.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Exports {
+  /// ES-DE : `gamelists/` et `downloaded_media/`.
+  #[serde(default)]
+  pub esde: bool,
+  #[serde(default)]
+  pub pegasus: Option<PegasusExport>,
+  #[serde(default)]
+  pub retroarch: Option<RetroArchExport>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -56,6 +453,37 @@ pub enum Source {
   #[serde(rename = "folder")]
   #[allow(dead_code)]
   Folder(FolderSource),
+  #[serde(rename = "http")]
+  Http(HttpSource),
+}
+
+impl Source {
+  /// Compile les motifs `filter` / `exclude` de la source.
+  fn load(mut self, system: &str) -> Result<Source> {
+    let compile = |filter: &[String], exclude: &[String], syntax, target| {
+      FileFilter::new(filter, exclude, syntax, target).map_err(|message| Error::InvalidFilter {
+        system: system.to_string(),
+        message,
+      })
+    };
+    match &mut self {
+      Source::InternetArchive(items) => {
+        for i in items.iter_mut() {
+          i.matcher = compile(&i.filter, &i.exclude, i.syntax, i.match_on)?;
+        }
+      }
+      Source::Folder(f) => f.matcher = compile(&f.filter, &f.exclude, f.syntax, f.match_on)?,
+      Source::Http(h) => {
+        if h.url.is_some() == h.manifest.is_some() {
+          return Err(Error::InvalidHttpSource {
+            system: system.to_string(),
+          });
+        }
+        h.matcher = compile(&h.filter, &h.exclude, h.syntax, h.match_on)?;
+      }
+    }
+    Ok(self)
+  }
 }
 
 /// Système brut — accepte l'ancien champ ia_items pour détecter la migration nécessaire
@@ -70,6 +498,79 @@ struct SystemRaw {
   #[serde(default)]
   #[serde(with = "serde_yaml::with::singleton_map_recursive")]
   pub source: Option<Source>,
+  #[serde(default)]
+  #[serde(with = "serde_yaml::with::singleton_map_recursive")]
+  pub sources: Vec<Source>,
+  #[serde(default)]
+  pub duplicates: DuplicatePolicy,
+  #[serde(default)]
+  pub collision: CollisionStrategy,
+  #[serde(default)]
+  #[serde(with = "serde_yaml::with::singleton_map")]
+  pub media_source: MediaSource,
+  #[serde(default)]
+  pub exports: Exports,
+  #[serde(default)]
+  pub extra_fields: Vec<GameField>,
+  #[serde(default)]
+  pub install_hooks: bool,
+  #[serde(default)]
+  pub launcher: Option<Launcher>,
+  #[serde(default)]
+  pub game_config: Vec<GameConfigRule>,
+  #[serde(default)]
+  pub bios: Option<Bios>,
+  #[serde(default)]
+  pub dat: Option<PathBuf>,
+  #[serde(default)]
+  pub one_game_one_rom: Option<OneGameOneRom>,
+}
+
+impl SystemRaw {
+  /// Valide et compile la configuration d'un système : lanceur, bios,
+  /// motifs des sources et des règles game_config.
+  fn load(self, confdir: &Path) -> Result<System> {
+    let launcher = match self.launcher {
+      Some(l) => Some(l.load(confdir, &self.name)?),
+      None if self.id == 214 => Some(Launcher::openbor().load(confdir, &self.name)?),
+      None => None,
+    };
+    let bios = self
+      .bios
+      .map(|b| b.load(self.depends.as_deref(), &self.name))
+      .transpose()?;
+    let sources = self
+      .source
+      .into_iter()
+      .chain(self.sources)
+      .map(|src| src.load(&self.name))
+      .collect::<Result<Vec<_>>>()?;
+    for (i, rule) in self.game_config.iter().enumerate() {
+      rule.check(&self.name, i)?;
+    }
+    if self.duplicates == DuplicatePolicy::Dat && self.dat.is_none() {
+      return Err(Error::MissingDat { system: self.name });
+    }
+    Ok(System {
+      name: self.name,
+      id: self.id,
+      basename: self.basename,
+      depends: self.depends,
+      dir: self.dir,
+      sources,
+      duplicates: self.duplicates,
+      collision: self.collision,
+      media_source: self.media_source,
+      exports: self.exports,
+      extra_fields: self.extra_fields,
+      install_hooks: self.install_hooks,
+      launcher,
+      game_config: self.game_config,
+      bios,
+      dat: self.dat.map(|p| confdir.join(p)),
+      one_game_one_rom: self.one_game_one_rom,
+    })
+  }
 }
 
 #[derive(Clone, Debug)]
@@ -79,7 +580,24 @@ pub struct System {
   pub basename: String,
   pub depends: Option<String>,
   pub dir: String,
-  pub source: Option<Source>,
+  /// `source` puis `sources`, par ordre de priorité.
+  pub sources: Vec<Source>,
+  pub duplicates: DuplicatePolicy,
+  pub collision: CollisionStrategy,
+  pub media_source: MediaSource,
+  pub exports: Exports,
+  pub extra_fields: Vec<GameField>,
+  /// Génère des scripts pacman `.install` qui mettent à jour `gamelist.xml`
+  /// à l'installation et à la suppression, au lieu des fichiers batoexec.
+  pub install_hooks: bool,
+  /// Lanceur par jeu ; OpenBOR (214) utilise le lanceur intégré par défaut.
+  pub launcher: Option<Launcher>,
+  pub game_config: Vec<GameConfigRule>,
+  pub bios: Option<Bios>,
+  /// DAT Logiqx (No-Intro, Redump…) restreignant la collecte aux dumps
+  /// connus ; chemin relatif au répertoire de configuration.
+  pub dat: Option<PathBuf>,
+  pub one_game_one_rom: Option<OneGameOneRom>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -93,7 +611,8 @@ struct ConfRaw {
 pub struct Conf {
   pub screenscraper: ScreenScraper,
   pub lang: Vec<String>,
-  pub systems: Vec<System>,
+  /// Systèmes par nom, avec leur erreur de configuration éventuelle.
+  pub systems: Vec<(String, Result<System>)>,
 }
 
 #[derive(Debug, Snafu)]
@@ -111,6 +630,47 @@ pub enum Error {
     backtrace: Backtrace,
     path: PathBuf,
   },
+  #[snafu(display("Could not read launcher template {}: {}", path.display(), source))]
+  ReadLauncher {
+    source: io::Error,
+    path: PathBuf,
+  },
+  #[snafu(display("Invalid launcher template for system {}: {}", system, source))]
+  InvalidLauncher {
+    source: minijinja::Error,
+    system: String,
+  },
+  #[snafu(display("Invalid source pattern for system {}: {}", system, message))]
+  InvalidFilter {
+    system: String,
+    message: String,
+  },
+  #[snafu(display(
+    "Invalid match pattern in game_config rule {} of system {}: {}",
+    rule,
+    system,
+    message
+  ))]
+  InvalidGameConfig {
+    system: String,
+    rule: usize,
+    message: String,
+  },
+  #[snafu(display(
+    "System {} has a bios section but neither bios.package nor depends",
+    system
+  ))]
+  MissingBiosPackage {
+    system: String,
+  },
+  #[snafu(display("HTTP source of system {} needs either url or manifest", system))]
+  InvalidHttpSource {
+    system: String,
+  },
+  #[snafu(display("System {} uses duplicates: dat but has no dat", system))]
+  MissingDat {
+    system: String,
+  },
   #[snafu(display("Configuration needs to be updated. Run: rompom --update-config"))]
   ConfigNeedsUpdate,
 }
@@ -131,17 +691,13 @@ impl Conf {
       return Err(Error::ConfigNeedsUpdate);
     }
 
+    let confdir = Path::new(file).parent().unwrap_or(Path::new("."));
+    // Un système invalide ne bloque pas les autres : son erreur n'est
+    // signalée que lorsqu'il est traité.
     let systems = raw
       .systems
       .into_iter()
-      .map(|s| System {
-        name: s.name,
-        id: s.id,
-        basename: s.basename,
-        depends: s.depends,
-        dir: s.dir,
-        source: s.source,
-      })
+      .map(|s| (s.name.clone(), s.load(confdir)))
       .collect();
 
     Ok(Conf {
@@ -151,7 +707,12 @@ impl Conf {
     })
   }
 
-  pub fn find_system(&self, name: &str) -> Option<System> {
-    self.systems.iter().find(|s| s.name == name).cloned()
+  /// `Some(Err(_))` quand la configuration du système est invalide.
+  pub fn find_system(&self, name: &str) -> Option<Result<System, &Error>> {
+    self
+      .systems
+      .iter()
+      .find(|(n, _)| n == name)
+      .map(|(_, system)| system.as_ref().cloned())
   }
 }
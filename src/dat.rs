@@ -0,0 +1,305 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs, io,
+  path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+  duplicates,
+  rom::{HttpSource, IaSource, RomSource, RomSourceData},
+  state::SystemState,
+};
+
+// ── Logiqx XML ─────────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+struct Datafile {
+  #[serde(rename = "game", alias = "machine", default)]
+  games: Vec<DatGame>,
+}
+
+#[derive(Deserialize)]
+struct DatGame {
+  #[serde(rename = "@name")]
+  name: String,
+  #[serde(rename = "@cloneof", default)]
+  cloneof: Option<String>,
+  #[serde(rename = "rom", default)]
+  roms: Vec<DatRom>,
+}
+
+#[derive(Deserialize)]
+struct DatRom {
+  #[serde(rename = "@name")]
+  name: String,
+  #[serde(rename = "@crc", default)]
+  crc: Option<String>,
+  #[serde(rename = "@md5", default)]
+  md5: Option<String>,
+  #[serde(rename = "@sha1", default)]
+  sha1: Option<String>,
+}
+
+/// A dump listed in the DAT.
+struct Entry {
+  /// Game (set) name, e.g. `Super Metroid (Japan, USA) (En,Ja)`.
+  game: String,
+  /// Parent set (`cloneof`), or `game` itself.
+  parent: String,
+  /// Canonical filename of the dump.
+  name: String,
+  crc: Option<String>,
+  md5: Option<String>,
+  sha1: Option<String>,
+}
+
+/// A loaded Logiqx DAT (No-Intro, Redump, …), indexed for lookups.
+pub struct Dat {
+  entries: Vec<Entry>,
+  by_sha1: HashMap<String, usize>,
+  by_md5: HashMap<String, usize>,
+  by_crc: HashMap<String, usize>,
+  by_name: HashMap<String, usize>,
+  by_game: HashMap<String, usize>,
+}
+
+/// How a source file relates to the DAT.
+enum Match {
+  /// Same content as a DAT dump (hash match).
+  Verified(usize),
+  /// Named like a DAT dump or set, but no hash could be compared (archive
+  /// of the set, or folder source not hashed yet).
+  Named(usize),
+  /// Named like a DAT dump but with different hashes.
+  Bad,
+  /// Unknown to the DAT.
+  Extra,
+}
+
+/// Outcome of the DAT filtering, for the summary and `<system>.dat.txt`.
+#[derive(Debug, Default)]
+pub struct DatReport {
+  /// Files whose hashes match a DAT dump.
+  pub verified: usize,
+  /// Files kept on their name alone (hashes not comparable).
+  pub unverified: usize,
+  /// `(source filename, canonical filename)` of verified files collected
+  /// under their DAT name.
+  pub renamed: Vec<(String, String)>,
+  /// Files named like a DAT dump whose hashes differ (not collected).
+  pub bad: Vec<String>,
+  /// Files unknown to the DAT (not collected).
+  pub extra: Vec<String>,
+  /// DAT sets with no matching file.
+  pub missing: Vec<String>,
+}
+
+fn lower(hash: &Option<String>) -> Option<String> {
+  hash
+    .as_deref()
+    .map(str::to_lowercase)
+    .filter(|h| !h.is_empty())
+}
+
+impl Dat {
+  pub fn load(path: &Path) -> io::Result<Dat> {
+    let xml = fs::read_to_string(path)?;
+    let datafile: Datafile = quick_xml::de::from_str(&xml).map_err(io::Error::other)?;
+
+    let mut dat = Dat {
+      entries: Vec::new(),
+      by_sha1: HashMap::new(),
+      by_md5: HashMap::new(),
+      by_crc: HashMap::new(),
+      by_name: HashMap::new(),
+      by_game: HashMap::new(),
+    };
+    for game in datafile.games {
+      for rom in game.roms {
+        let idx = dat.entries.len();
+        let entry = Entry {
+          game: game.name.clone(),
+          parent: game.cloneof.clone().unwrap_or_else(|| game.name.clone()),
+          name: rom.name,
+          crc: lower(&rom.crc),
+          md5: lower(&rom.md5),
+          sha1: lower(&rom.sha1),
+        };
+        if let Some(ref h) = entry.sha1 {
+          dat.by_sha1.entry(h.clone()).or_insert(idx);
+        }
+        if let Some(ref h) = entry.md5 {
+          dat.by_md5.entry(h.clone()).or_insert(idx);
+        }
+        if let Some(ref h) = entry.crc {
+          dat.by_crc.entry(h.clone()).or_insert(idx);
+        }
+        dat.by_name.entry(entry.name.clone()).or_insert(idx);
+        dat.by_game.entry(entry.game.clone()).or_insert(idx);
+        dat.entries.push(entry);
+      }
+    }
+    Ok(dat)
+  }
+
+  /// True when a dump of the DAT has this SHA-1.
+  pub fn knows_sha1(&self, sha1: &str) -> bool {
+    self.by_sha1.contains_key(&sha1.to_lowercase())
+  }
+
+  /// Parent set of a file known by its dump or set name, for 1G1R grouping.
+  pub fn parent(&self, filename: &str) -> Option<&str> {
+    let stem = Path::new(filename)
+      .file_stem()
+      .and_then(|s| s.to_str())
+      .unwrap_or(filename);
+    self
+      .by_name
+      .get(filename)
+      .or_else(|| self.by_game.get(stem))
+      .map(|&idx| self.entries[idx].parent.as_str())
+  }
+
+  /// Matches a file by hash first (strongest hash available on both sides),
+  /// then by dump name, then by set name for archives (`<set>.zip`).
+  fn identify(
+    &self,
+    filename: &str,
+    crc: &Option<String>,
+    md5: &Option<String>,
+    sha1: &Option<String>,
+  ) -> Match {
+    let (crc, md5, sha1) = (lower(crc), lower(md5), lower(sha1));
+    let by_hash = sha1
+      .as_ref()
+      .and_then(|h| self.by_sha1.get(h))
+      .or_else(|| md5.as_ref().and_then(|h| self.by_md5.get(h)))
+      .or_else(|| crc.as_ref().and_then(|h| self.by_crc.get(h)));
+    if let Some(&idx) = by_hash {
+      return Match::Verified(idx);
+    }
+
+    if let Some(&idx) = self.by_name.get(filename) {
+      let entry = &self.entries[idx];
+      let comparable = [(&sha1, &entry.sha1), (&md5, &entry.md5), (&crc, &entry.crc)]
+        .into_iter()
+        .any(|(ours, theirs)| ours.is_some() && theirs.is_some());
+      return if comparable {
+        Match::Bad
+      } else {
+        Match::Named(idx)
+      };
+    }
+
+    let stem = Path::new(filename)
+      .file_stem()
+      .and_then(|s| s.to_str())
+      .unwrap_or(filename);
+    match self.by_game.get(stem) {
+      Some(&idx) => Match::Named(idx),
+      None => Match::Extra,
+    }
+  }
+}
+
+/// Keeps only the sources matching a DAT dump, renaming verified ones to
+/// their canonical DAT filename.
+///
+/// Internet Archive and HTTP files are checked against the crc/md5/sha1 of the
+/// item metadata or manifest; folder files against their sha1, taken from
+/// state when their size and mtime are unchanged and hashed otherwise (see
+/// `duplicates::sha1`). Runs before multi-disc grouping, so each disc is
+/// checked on its own.
+pub fn filter_sources(
+  sources: &mut Vec<RomSourceData>,
+  dat: &Dat,
+  state: &SystemState,
+) -> DatReport {
+  let mut report = DatReport::default();
+  let mut found_games: HashSet<&str> = HashSet::new();
+  let mut names: HashSet<String> = sources.iter().map(|s| s.filename.clone()).collect();
+
+  sources.retain_mut(|source| {
+    let found = match &source.source {
+      RomSource::InternetArchive(IaSource {
+        crc32, md5, sha1, ..
+      })
+      | RomSource::Http(HttpSource {
+        crc32, md5, sha1, ..
+      }) => dat.identify(&source.filename, crc32, md5, sha1),
+      RomSource::Folder(_) => dat.identify(
+        &source.filename,
+        &None,
+        &None,
+        &duplicates::sha1(source, state),
+      ),
+    };
+    match found {
+      Match::Verified(idx) => {
+        let entry = &dat.entries[idx];
+        found_games.insert(&entry.game);
+        report.verified += 1;
+        // Keep the source name if the canonical one is taken (duplicate dump)
+        // or is not a plain filename.
+        if entry.name != source.filename
+          && !entry.name.contains(['/', '\\'])
+          && names.insert(entry.name.clone())
+        {
+          report
+            .renamed
+            .push((source.filename.clone(), entry.name.clone()));
+          source.filename = entry.name.clone();
+        }
+        true
+      }
+      Match::Named(idx) => {
+        found_games.insert(&dat.entries[idx].game);
+        report.unverified += 1;
+        true
+      }
+      Match::Bad => {
+        report.bad.push(source.filename.clone());
+        false
+      }
+      Match::Extra => {
+        report.extra.push(source.filename.clone());
+        false
+      }
+    }
+  });
+
+  let mut missing: Vec<String> = dat
+    .entries
+    .iter()
+    .map(|e| e.game.as_str())
+    .filter(|g| !found_games.contains(g))
+    .collect::<HashSet<_>>()
+    .into_iter()
+    .map(str::to_string)
+    .collect();
+  missing.sort();
+  report.missing = missing;
+  report.bad.sort();
+  report.extra.sort();
+  report
+}
+
+/// Writes the full DAT report (renamed, bad, extra and missing dumps).
+pub fn write_report(path: &str, report: &DatReport) -> io::Result<()> {
+  let mut out = String::new();
+  for (from, to) in &report.renamed {
+    out.push_str(&format!("renamed  {} → {}\n", from, to));
+  }
+  for (label, names) in [
+    ("bad     ", &report.bad),
+    ("extra   ", &report.extra),
+    ("missing ", &report.missing),
+  ] {
+    for name in names {
+      out.push_str(&format!("{} {}\n", label, name));
+    }
+  }
+  fs::write(path, out)
+}
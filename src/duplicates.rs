@@ -0,0 +1,155 @@
+use std::{collections::HashMap, fs, io, time::UNIX_EPOCH};
+
+use checksums::{hash_file, Algorithm};
+
+use crate::{
+  conf::DuplicatePolicy,
+  dat::Dat,
+  rom::{HttpSource, IaSource, RomSource, RomSourceData},
+  state::SystemState,
+};
+
+/// A file left out because another source provides the same ROM.
+pub struct Duplicate {
+  pub filename: String,
+  pub origin: String,
+  /// `(filename, origin)` of the copy kept.
+  pub kept: (String, String),
+}
+
+/// A ROM collected from another source than in the previous run.
+pub struct OriginChange {
+  pub filename: String,
+  pub previous: String,
+  pub current: String,
+}
+
+/// SHA-1 of a collected file: from the IA metadata, from state when the
+/// folder file has the recorded size and mtime (as `ComputeHashes` does),
+/// hashed otherwise.
+pub fn sha1(source: &RomSourceData, state: &SystemState) -> Option<String> {
+  let folder = match &source.source {
+    RomSource::InternetArchive(IaSource { sha1, .. })
+    | RomSource::Http(HttpSource { sha1, .. }) => return sha1.as_deref().map(str::to_lowercase),
+    RomSource::Folder(f) => f,
+  };
+  let meta = fs::metadata(&folder.local_path).ok()?;
+  let mtime = meta
+    .modified()
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_secs());
+  let cached = state
+    .roms
+    .get(&source.filename)
+    .filter(|e| e.rom_mtime != 0 && Some(e.rom_mtime) == mtime && e.rom_size == meta.len());
+  match cached {
+    Some(entry) => Some(entry.rom_sha1.clone()),
+    None => Some(hash_file(&folder.local_path, Algorithm::SHA1).to_lowercase()),
+  }
+}
+
+/// Keeps one copy of each ROM provided by several sources, `sources` being
+/// in source priority order.
+///
+/// Files sharing a filename are always duplicates (the filename is the state
+/// key): the first one wins, or with `DuplicatePolicy::Dat` the first one
+/// whose SHA-1 is in the DAT. `DuplicatePolicy::Sha1` also drops files with
+/// the same content as an earlier one under another name. Runs before DAT
+/// filtering and 1G1R selection.
+pub fn resolve(
+  sources: &mut Vec<RomSourceData>,
+  policy: DuplicatePolicy,
+  dat: Option<&Dat>,
+  state: &SystemState,
+) -> Vec<Duplicate> {
+  let hashes: Vec<Option<String>> = match policy {
+    DuplicatePolicy::Order => vec![None; sources.len()],
+    DuplicatePolicy::Sha1 | DuplicatePolicy::Dat => {
+      sources.iter().map(|s| sha1(s, state)).collect()
+    }
+  };
+  let verified = |i: usize| {
+    dat
+      .zip(hashes[i].as_deref())
+      .is_some_and(|(dat, sha1)| dat.knows_sha1(sha1))
+  };
+
+  let mut keep = vec![true; sources.len()];
+  // (dropped, kept) indexes.
+  let mut dropped: Vec<(usize, usize)> = Vec::new();
+  let mut by_name: HashMap<&str, usize> = HashMap::new();
+  let mut by_sha1: HashMap<&str, usize> = HashMap::new();
+  for (i, source) in sources.iter().enumerate() {
+    if policy == DuplicatePolicy::Sha1 {
+      if let Some(&k) = hashes[i].as_deref().and_then(|h| by_sha1.get(h)) {
+        keep[i] = false;
+        dropped.push((i, k));
+        continue;
+      }
+    }
+    match by_name.get(source.filename.as_str()) {
+      None => {
+        by_name.insert(&source.filename, i);
+      }
+      Some(&k) if policy == DuplicatePolicy::Dat && verified(i) && !verified(k) => {
+        keep[k] = false;
+        dropped.push((k, i));
+        by_name.insert(&source.filename, i);
+      }
+      Some(&k) => {
+        keep[i] = false;
+        dropped.push((i, k));
+        continue;
+      }
+    }
+    if let Some(h) = hashes[i].as_deref() {
+      by_sha1.entry(h).or_insert(i);
+    }
+  }
+
+  let duplicates = dropped
+    .into_iter()
+    .map(|(i, k)| Duplicate {
+      filename: sources[i].filename.clone(),
+      origin: sources[i].origin.clone(),
+      kept: (sources[k].filename.clone(), sources[k].origin.clone()),
+    })
+    .collect();
+  let mut index = 0;
+  sources.retain(|_| {
+    index += 1;
+    keep[index - 1]
+  });
+  duplicates
+}
+
+/// ROMs whose origin differs from the one recorded in state. Their packages
+/// are rebuilt (sources and URLs change).
+pub fn origin_changes(sources: &[RomSourceData], state: &SystemState) -> Vec<OriginChange> {
+  sources
+    .iter()
+    .filter_map(|source| {
+      let previous = state.roms.get(&source.filename)?.origin.as_ref()?;
+      (*previous != source.origin).then(|| OriginChange {
+        filename: source.filename.clone(),
+        previous: previous.clone(),
+        current: source.origin.clone(),
+      })
+    })
+    .collect()
+}
+
+/// Writes every dropped duplicate and the copy kept instead.
+pub fn write_report(path: &str, duplicates: &[Duplicate]) -> io::Result<()> {
+  let out: String = duplicates
+    .iter()
+    .map(|d| {
+      format!(
+        "{}  ({})  → {}  ({})\n",
+        d.filename, d.origin, d.kept.0, d.kept.1
+      )
+    })
+    .collect();
+  fs::write(path, out)
+}
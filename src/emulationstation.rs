@@ -1,10 +1,12 @@
 use chrono::prelude::*;
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 
-use screenscraper::jeuinfo::JeuInfo;
+use screenscraper::jeuinfo::{Genre, JeuInfo};
 
-#[derive(Serialize, Debug)]
-#[serde(rename = "game")]
+use crate::conf::GameField;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename = "game", default)]
 pub struct Game {
   pub path: String,
   pub name: String,
@@ -17,6 +19,20 @@ pub struct Game {
   pub players: String,
   pub region: String,
   #[serde(skip_serializing_if = "Option::is_none")]
+  pub md5: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hash: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub lang: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub family: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub genreid: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub kidgame: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub arcadesystemname: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub image: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub thumbnail: Option<String>,
@@ -33,6 +49,11 @@ pub struct Game {
 }
 
 impl Game {
+  /// Parses a `<game>` entry as written to `description.xml`.
+  pub fn from_xml(xml: &str) -> Option<Game> {
+    quick_xml::de::from_str(xml).ok()
+  }
+
   pub fn from_jeuinfo(jeu: &Option<JeuInfo>, path: &str, lang: &[&str]) -> Game {
     let region_fav = &["wor", "eu", "us", "fr", "jp", "ss"];
 
@@ -103,6 +124,13 @@ impl Game {
       genre,
       players,
       region,
+      md5: None,
+      hash: None,
+      lang: None,
+      family: None,
+      genreid: None,
+      kidgame: None,
+      arcadesystemname: None,
       image: None,
       thumbnail: None,
       video: None,
@@ -112,4 +140,75 @@ impl Game {
       manual: None,
     }
   }
+
+  /// Fills the optional fields enabled in `fields` from ScreenScraper data and
+  /// the ROM hashes. Fields whose source is unknown are left out.
+  pub fn apply_extra_fields(
+    &mut self,
+    fields: &[GameField],
+    jeu: &Option<JeuInfo>,
+    md5: Option<&str>,
+    crc32: Option<&str>,
+    lang: &[&str],
+  ) {
+    for field in fields {
+      match field {
+        GameField::Md5 => self.md5 = md5.map(str::to_lowercase),
+        GameField::Hash => self.hash = crc32.map(str::to_uppercase),
+        GameField::Lang => {
+          self.lang = jeu
+            .as_ref()
+            .and_then(|x| x.rom.as_ref())
+            .and_then(|y| y.langues.as_ref())
+            .map(|z| z.langues_shortname.join(","))
+            .filter(|l| !l.is_empty())
+        }
+        GameField::Family => {
+          self.family = jeu
+            .as_ref()
+            .and_then(|x| x.familles.as_ref())
+            .and_then(|f| f.first())
+            .and_then(|f| localized_name(f, lang))
+        }
+        GameField::GenreId => {
+          self.genreid = jeu
+            .as_ref()
+            .and_then(|x| x.genres.as_ref())
+            .and_then(|g| g.first())
+            .map(|g| g.id.clone())
+        }
+        GameField::KidGame => {
+          self.kidgame = jeu
+            .as_ref()
+            .and_then(|x| x.classifications.as_ref())
+            .filter(|c| !c.is_empty())
+            .map(|c| c.iter().any(|c| is_kid_rating(&c.type_, &c.text)))
+        }
+        // ScreenScraper reports the game's platform (`systeme`), not the
+        // arcade board: the tag stays unset rather than carry the wrong one.
+        GameField::ArcadeSystemName => self.arcadesystemname = None,
+      }
+    }
+  }
+}
+
+/// Name of a genre/family in the first preferred language available.
+fn localized_name(genre: &Genre, lang: &[&str]) -> Option<String> {
+  lang
+    .iter()
+    .find_map(|l| genre.noms.iter().find(|n| n.langue == *l))
+    .or_else(|| genre.noms.first())
+    .map(|n| n.text.clone())
+}
+
+/// True when an age classification marks the game as suitable for children
+/// (PEGI 3/7, ESRB E/EC, CERO A, USK 0/6).
+fn is_kid_rating(kind: &str, rating: &str) -> bool {
+  match kind {
+    "PEGI" => matches!(rating, "3" | "7"),
+    "ESRB" => matches!(rating, "E" | "EC"),
+    "CERO" => rating == "A",
+    "USK" => matches!(rating, "0" | "6"),
+    _ => false,
+  }
 }
@@ -0,0 +1,59 @@
+use std::{fs, io, path::Path};
+
+use crate::{frontend::link_or_copy, frontend::FrontendEntry, gamelist, package};
+
+/// rompom media kind → ES-DE `downloaded_media` folder.
+///
+/// ES-DE "marquees" are game logos, which ScreenScraper calls wheels.
+const MEDIA_FOLDERS: &[(&str, &str)] = &[
+  ("thumbnail", "covers"),
+  ("image", "titlescreens"),
+  ("screenshot", "screenshots"),
+  ("wheel", "marquees"),
+  ("video", "videos"),
+  ("manual", "manuals"),
+];
+
+/// Writes an ES-DE tree under `root`:
+///
+/// - `gamelists/<dir>/gamelist.xml`, without media tags (ES-DE locates media
+///   by filename),
+/// - `downloaded_media/<dir>/<folder>/<rom stem>.<ext>` for each media.
+///
+/// Returns the number of games written.
+pub fn export(root: &Path, dir: &str, entries: &[FrontendEntry]) -> io::Result<usize> {
+  let gamelist_dir = root.join("gamelists").join(dir);
+  let media_dir = root.join("downloaded_media").join(dir);
+  fs::create_dir_all(&gamelist_dir)?;
+
+  let mut games = Vec::new();
+  for entry in entries {
+    let mut game = entry.game.clone();
+    game.image = None;
+    game.thumbnail = None;
+    game.video = None;
+    game.marquee = None;
+    game.screenshot = None;
+    game.wheel = None;
+    game.manual = None;
+    games.push(package::generate_description_xml(&game));
+
+    let stem = entry.rom_stem();
+    for &(kind, folder) in MEDIA_FOLDERS {
+      let Some(src) = entry.media(kind) else {
+        continue;
+      };
+      let ext = src.extension().and_then(|e| e.to_str()).unwrap_or_default();
+      link_or_copy(
+        &src,
+        &media_dir.join(folder).join(format!("{}.{}", stem, ext)),
+      )?;
+    }
+  }
+
+  fs::write(
+    gamelist_dir.join("gamelist.xml"),
+    gamelist::render_gamelist(games.iter().map(|g| g.trim())),
+  )?;
+  Ok(games.len())
+}
@@ -0,0 +1,91 @@
+use std::{fmt::Write, fs, path::Path, time::UNIX_EPOCH};
+
+use checksums::{hash_reader, Algorithm};
+
+use crate::{
+  conf::System,
+  package::PKGBUILD_TEMPLATES,
+  rom::{HttpSource, IaSource, RomSource, RomSourceData},
+  state::SystemState,
+};
+
+/// `size:mtime` of a local file, as the folder fast path of `ComputeHashes`
+/// compares them; empty when the file cannot be read.
+fn file_stamp(path: &Path) -> String {
+  let Ok(meta) = fs::metadata(path) else {
+    return String::new();
+  };
+  let mtime = meta
+    .modified()
+    .ok()
+    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_secs())
+    .unwrap_or(0);
+  format!("{}:{}", meta.len(), mtime)
+}
+
+/// Fingerprint of everything a run builds packages from, apart from
+/// ScreenScraper: the collected files (names, package names, origins, URLs
+/// and SHA-1s, or size and mtime for local files), the system configuration,
+/// the languages, and the PKGBUILD templates of this rompom version.
+///
+/// Two runs with the same fingerprint write the same PKGBUILDs, so a
+/// successful run records it in state and the next one can stop early.
+pub fn compute(sources: &[RomSourceData], system: &System, lang: &[String]) -> String {
+  let mut input = String::new();
+  writeln!(input, "rompom {}", env!("CARGO_PKG_VERSION")).ok();
+  for template in PKGBUILD_TEMPLATES {
+    writeln!(input, "{}", template).ok();
+  }
+  writeln!(input, "{:?}", system).ok();
+  writeln!(input, "{:?}", lang).ok();
+
+  let mut sorted: Vec<&RomSourceData> = sources.iter().collect();
+  sorted.sort_by(|a, b| a.filename.cmp(&b.filename));
+  for source in sorted {
+    let identity = match &source.source {
+      RomSource::InternetArchive(IaSource { rom_url, sha1, .. })
+      | RomSource::Http(HttpSource { rom_url, sha1, .. }) => {
+        format!("{} {}", rom_url, sha1.as_deref().unwrap_or(""))
+      }
+      RomSource::Folder(f) => format!(
+        "{} {}",
+        f.mirror.as_deref().unwrap_or(""),
+        file_stamp(&f.local_path)
+      ),
+    };
+    writeln!(
+      input,
+      "{}\t{}\t{}\t{}\t{:?}\t{}",
+      source.filename, source.file_name, source.romname, source.origin, source.game_dir, identity
+    )
+    .ok();
+    for disc in &source.extra_discs {
+      let identity = match &disc.local_path {
+        Some(path) => file_stamp(path),
+        None => format!("{} {}", disc.rom_url, disc.sha1.as_deref().unwrap_or("")),
+      };
+      writeln!(
+        input,
+        "\t{}\t{}\t{}",
+        disc.filename, disc.file_name, identity
+      )
+      .ok();
+    }
+  }
+  hash_reader(&mut input.as_bytes(), Algorithm::SHA1).to_lowercase()
+}
+
+/// Whether the previous run of `sources` completed with the same fingerprint
+/// and left a PKGBUILD for every ROM, so that running again would change
+/// nothing.
+pub fn unchanged(fingerprint: &str, sources: &[RomSourceData], state: &SystemState) -> bool {
+  state.fingerprint.as_deref() == Some(fingerprint)
+    && sources.iter().all(|source| {
+      state.roms.contains_key(&source.filename)
+        && Path::new(&source.filename)
+          .with_extension("")
+          .join("PKGBUILD")
+          .is_file()
+    })
+}
@@ -0,0 +1,228 @@
+use std::{
+  collections::{hash_map::Entry, HashMap, HashSet},
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  conf,
+  package::encode_path_segment,
+  rom::{DiscFile, FolderSource, RomSource, RomSourceData},
+};
+
+/// An entry of a folder source left out of the collection.
+pub enum Skipped {
+  /// Name not valid UTF-8: it could not be written to a PKGBUILD.
+  NonUtf8(PathBuf),
+  /// Same filename as `kept`, found earlier in the tree: the filename is the
+  /// ROM's state key and output directory, so only the first is collected.
+  SameName { path: PathBuf, kept: PathBuf },
+}
+
+/// Directory entries sorted by name, as `(path, name)`. Entries whose name
+/// is not valid UTF-8 are left out and appended to `skipped`.
+fn sorted_entries(dir: &Path, skipped: &mut Vec<Skipped>) -> io::Result<Vec<(PathBuf, String)>> {
+  let mut entries: Vec<(PathBuf, String)> = fs::read_dir(dir)?
+    .filter_map(|e| e.ok())
+    .filter_map(|e| match e.file_name().into_string() {
+      Ok(name) => Some((e.path(), name)),
+      Err(_) => {
+        skipped.push(Skipped::NonUtf8(e.path()));
+        None
+      }
+    })
+    .collect();
+  entries.sort_by(|a, b| a.1.cmp(&b.1));
+  Ok(entries)
+}
+
+fn join_rel(rel: &str, name: &str) -> String {
+  if rel.is_empty() {
+    name.to_string()
+  } else {
+    format!("{}/{}", rel, name)
+  }
+}
+
+/// PKGBUILD URL of the file at `rel_path` in a folder source:
+/// `<mirror>/<rel_path>`, each segment percent-encoded, when a mirror is
+/// configured; empty (local source) otherwise.
+pub fn mirror_url(mirror: Option<&str>, rel_path: &str) -> String {
+  mirror
+    .map(|m| {
+      let path = rel_path
+        .split('/')
+        .map(encode_path_segment)
+        .collect::<Vec<_>>()
+        .join("/");
+      format!("{}/{}", m.trim_end_matches('/'), path)
+    })
+    .unwrap_or_default()
+}
+
+fn rom_source(
+  folder: &conf::FolderSource,
+  path: PathBuf,
+  rel_path: String,
+  filename: String,
+) -> RomSourceData {
+  RomSourceData {
+    file_name: path.to_string_lossy().into_owned(),
+    filename,
+    source: RomSource::Folder(FolderSource {
+      local_path: path,
+      rel_path,
+      mirror: folder.mirror.clone(),
+    }),
+    extra_discs: Vec::new(),
+    romname: String::new(),
+    game_dir: None,
+    origin: folder.origin(),
+  }
+}
+
+/// Whether to descend into the directory `path`: symlinked directories only
+/// with `follow_symlinks`, and each directory once (symlinks may point back
+/// to an ancestor).
+fn enter(
+  folder: &conf::FolderSource,
+  path: &Path,
+  visited: &mut HashSet<PathBuf>,
+) -> io::Result<bool> {
+  let is_symlink = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+  if is_symlink && !folder.follow_symlinks {
+    return Ok(false);
+  }
+  Ok(visited.insert(fs::canonicalize(path)?))
+}
+
+/// Appends the files under `dir`, subdirectories included, to `files` as
+/// `(path, path relative to the game folder)`.
+fn game_files(
+  folder: &conf::FolderSource,
+  dir: &Path,
+  rel: &str,
+  visited: &mut HashSet<PathBuf>,
+  files: &mut Vec<(PathBuf, String)>,
+  skipped: &mut Vec<Skipped>,
+) -> io::Result<()> {
+  for (path, name) in sorted_entries(dir, skipped)? {
+    let rel_path = join_rel(rel, &name);
+    if path.is_file() {
+      files.push((path, rel_path));
+    } else if path.is_dir() && enter(folder, &path, visited)? {
+      game_files(folder, &path, &rel_path, visited, files, skipped)?;
+    }
+  }
+  Ok(())
+}
+
+/// Turns a game folder into one ROM: the first file at its top level matching
+/// the source patterns is the main file, every other file of the folder,
+/// subdirectories included, comes along under its relative path. `None` when
+/// no file matches.
+fn game_folder(
+  folder: &conf::FolderSource,
+  dir: &Path,
+  rel: &str,
+  visited: &mut HashSet<PathBuf>,
+  skipped: &mut Vec<Skipped>,
+) -> io::Result<Option<RomSourceData>> {
+  let mut files = Vec::new();
+  game_files(folder, dir, "", visited, &mut files, skipped)?;
+  let Some(main) = files
+    .iter()
+    .position(|(_, name)| !name.contains('/') && folder.matcher.matches(&join_rel(rel, name)))
+  else {
+    return Ok(None);
+  };
+  let (path, filename) = files.remove(main);
+  let mut source = rom_source(folder, path, join_rel(rel, &filename), filename);
+  source.extra_discs = files
+    .into_iter()
+    .map(|(path, name)| DiscFile {
+      file_name: path.to_string_lossy().into_owned(),
+      rom_url: mirror_url(folder.mirror.as_deref(), &join_rel(rel, &name)),
+      filename: name,
+      sha1: None,
+      md5: None,
+      crc32: None,
+      size: 0,
+      local_path: Some(path),
+    })
+    .collect();
+  source.game_dir = dir.file_name().and_then(|n| n.to_str()).map(str::to_string);
+  Ok(Some(source))
+}
+
+/// Walks `dir` (at `depth` below the source root, `rel` being its relative
+/// path) and appends the ROMs found to `out`, and the entries left out to
+/// `skipped`.
+fn walk(
+  folder: &conf::FolderSource,
+  dir: &Path,
+  rel: &str,
+  depth: usize,
+  visited: &mut HashSet<PathBuf>,
+  out: &mut Vec<RomSourceData>,
+  skipped: &mut Vec<Skipped>,
+) -> io::Result<()> {
+  let recursive = folder.recursive || folder.game_dir_depth.is_some();
+  for (path, name) in sorted_entries(dir, skipped)? {
+    let rel_path = join_rel(rel, &name);
+    if path.is_file() {
+      if folder.matcher.matches(&rel_path) {
+        out.push(rom_source(folder, path, rel_path, name));
+      }
+      continue;
+    }
+    if !path.is_dir() || !recursive || !enter(folder, &path, visited)? {
+      continue;
+    }
+    let child_depth = depth + 1;
+    if folder.max_depth.is_some_and(|max| child_depth > max) {
+      continue;
+    }
+    if folder.game_dir_depth == Some(child_depth) {
+      out.extend(game_folder(folder, &path, &rel_path, visited, skipped)?);
+    } else {
+      walk(folder, &path, &rel_path, child_depth, visited, out, skipped)?;
+    }
+  }
+  Ok(())
+}
+
+/// Collects the ROMs of a folder source.
+///
+/// Only the top level is scanned unless `recursive` (or `game_dir_depth`) is
+/// set; `max_depth` then bounds the descent. Symlinked directories are
+/// skipped unless `follow_symlinks`. Directories at `game_dir_depth` become
+/// one ROM each with all the files below them, whatever `max_depth` (see
+/// `game_folder`).
+///
+/// Returns the ROMs and the entries left out (see `Skipped`).
+pub fn collect(folder: &conf::FolderSource) -> io::Result<(Vec<RomSourceData>, Vec<Skipped>)> {
+  let root = Path::new(&folder.path);
+  let mut visited = HashSet::from([fs::canonicalize(root)?]);
+  let mut out = Vec::new();
+  let mut skipped = Vec::new();
+  walk(folder, root, "", 0, &mut visited, &mut out, &mut skipped)?;
+  let mut seen: HashMap<String, PathBuf> = HashMap::new();
+  out.retain(|source| {
+    let path = PathBuf::from(&source.file_name);
+    match seen.entry(source.filename.clone()) {
+      Entry::Vacant(e) => {
+        e.insert(path);
+        true
+      }
+      Entry::Occupied(e) => {
+        skipped.push(Skipped::SameName {
+          path,
+          kept: e.get().clone(),
+        });
+        false
+      }
+    }
+  });
+  Ok((out, skipped))
+}
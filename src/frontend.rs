@@ -0,0 +1,126 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  conf::System, emulationstation::Game, esde, gamelist, pegasus, retroarch, state::SystemState,
+};
+
+/// Everything a frontend exporter needs to know about one packaged ROM.
+pub struct FrontendEntry {
+  /// Game metadata, as shipped in the package `description.xml`.
+  pub game: Game,
+  /// Package directory holding the downloaded media (`<kind>.<ext>`).
+  pub directory: PathBuf,
+  /// ROM crc32 (lowercase hex), when known.
+  pub crc32: Option<String>,
+}
+
+impl FrontendEntry {
+  /// ROM path relative to the system ROM directory (`game.path` without `./`).
+  pub fn rom_path(&self) -> &str {
+    self.game.path.strip_prefix("./").unwrap_or(&self.game.path)
+  }
+
+  /// ROM path without extension, used to name per-game media files.
+  pub fn rom_stem(&self) -> String {
+    Path::new(self.rom_path())
+      .with_extension("")
+      .to_string_lossy()
+      .into_owned()
+  }
+
+  /// Returns the downloaded media file of `kind` (`thumbnail`, `video`, …).
+  pub fn media(&self, kind: &str) -> Option<PathBuf> {
+    fs::read_dir(&self.directory)
+      .ok()?
+      .filter_map(|e| e.ok().map(|e| e.path()))
+      .find(|p| p.is_file() && p.file_stem().and_then(|s| s.to_str()) == Some(kind))
+  }
+}
+
+/// Loads one `FrontendEntry` per ROM recorded in state, sorted by filename.
+/// ROMs whose package has no `description.xml` yet are skipped.
+pub fn collect_entries(state: &SystemState) -> Vec<FrontendEntry> {
+  let mut filenames: Vec<&String> = state.roms.keys().collect();
+  filenames.sort();
+  filenames
+    .into_iter()
+    .filter_map(|filename| {
+      let game = gamelist::read_game_entry(filename).and_then(|xml| Game::from_xml(&xml))?;
+      Some(FrontendEntry {
+        game,
+        directory: Path::new(filename).with_extension(""),
+        crc32: state.roms[filename].rom_crc32.clone(),
+      })
+    })
+    .collect()
+}
+
+/// Places `src` at `dest`, hard-linking when possible to avoid duplicating
+/// media, copying otherwise (different filesystem).
+pub fn link_or_copy(src: &Path, dest: &Path) -> io::Result<()> {
+  if let Some(parent) = dest.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  if dest.exists() {
+    fs::remove_file(dest)?;
+  }
+  if fs::hard_link(src, dest).is_err() {
+    fs::copy(src, dest)?;
+  }
+  Ok(())
+}
+
+/// Result of one frontend export, for the end-of-run summary.
+pub struct FrontendExport {
+  pub frontend: &'static str,
+  pub path: PathBuf,
+  pub result: io::Result<usize>,
+}
+
+/// Runs every exporter enabled in the system `exports` configuration.
+///
+/// Each frontend gets its own tree under `<system>.export/<frontend>/`,
+/// laid out so it can be copied as-is onto the target device.
+pub fn run_exports(system: &System, state: &SystemState) -> Vec<FrontendExport> {
+  let exports = &system.exports;
+  if !exports.esde && exports.pegasus.is_none() && exports.retroarch.is_none() {
+    return Vec::new();
+  }
+
+  let entries = collect_entries(state);
+  let root = PathBuf::from(format!("{}.export", system.name));
+  let mut results = Vec::new();
+
+  if exports.esde {
+    let path = root.join("esde");
+    let result = esde::export(&path, &system.dir, &entries);
+    results.push(FrontendExport {
+      frontend: "ES-DE",
+      path,
+      result,
+    });
+  }
+  if let Some(ref conf) = exports.pegasus {
+    let path = root.join("pegasus");
+    let result = pegasus::export(&path, system, conf, &entries);
+    results.push(FrontendExport {
+      frontend: "Pegasus",
+      path,
+      result,
+    });
+  }
+  if let Some(ref conf) = exports.retroarch {
+    let path = root.join("retroarch");
+    let result = retroarch::export(&path, conf, &entries);
+    results.push(FrontendExport {
+      frontend: "RetroArch",
+      path,
+      result,
+    });
+  }
+
+  results
+}
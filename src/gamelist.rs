@@ -0,0 +1,126 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
+use crate::{orphans::Orphan, state::SystemState};
+
+/// Reads the `<game>` entry that `Package::build` wrote for a ROM.
+///
+/// Returns `None` if the package directory has no `description.xml` yet.
+pub fn read_game_entry(filename: &str) -> Option<String> {
+  let directory = Path::new(filename).with_extension("");
+  fs::read_to_string(directory.join("description.xml"))
+    .ok()
+    .map(|xml| xml.trim().to_string())
+    .filter(|xml| !xml.is_empty())
+}
+
+/// Wraps `<game>` entries into an EmulationStation `gamelist.xml` document.
+pub fn render_gamelist<'a>(entries: impl IntoIterator<Item = &'a str>) -> String {
+  let mut xml = String::from("<?xml version=\"1.0\"?>\n<gameList>\n");
+  for entry in entries {
+    for line in entry.lines() {
+      xml.push_str("  ");
+      xml.push_str(line);
+      xml.push('\n');
+    }
+  }
+  xml.push_str("</gameList>\n");
+  xml
+}
+
+/// Returns the unescaped `<path>` of a `<game>` entry.
+pub fn game_path(entry: &str) -> Option<String> {
+  let start = entry.find("<path>")? + "<path>".len();
+  let end = start + entry[start..].find("</path>")?;
+  let path = entry[start..end].trim();
+  Some(
+    quick_xml::escape::unescape(path)
+      .map(|p| p.into_owned())
+      .unwrap_or_else(|_| path.to_string()),
+  )
+}
+
+/// Splits the children of `<gameList>` into `<game>` / `<folder>` blocks,
+/// dedented to the level of their opening tag.
+pub fn gamelist_blocks(xml: &str) -> Vec<String> {
+  let mut blocks = Vec::new();
+  let mut rest = xml;
+  loop {
+    let next = ["<game", "<folder"]
+      .iter()
+      .filter_map(|tag| {
+        rest
+          .match_indices(tag)
+          .find(|(i, _)| {
+            let after = rest[i + tag.len()..].chars().next();
+            matches!(after, Some('>' | ' ' | '\t' | '\n' | '\r'))
+          })
+          .map(|(i, _)| (i, *tag))
+      })
+      .min_by_key(|(i, _)| *i);
+    let Some((start, tag)) = next else { break };
+    let close = format!("</{}>", &tag[1..]);
+    let Some(len) = rest[start..].find(&close) else {
+      break;
+    };
+    let end = start + len + close.len();
+    let indent = rest[..start]
+      .rsplit('\n')
+      .next()
+      .filter(|l| l.trim().is_empty())
+      .map(str::len)
+      .unwrap_or(0);
+    let block: Vec<&str> = rest[start..end]
+      .lines()
+      .map(|l| {
+        let ws = l.len() - l.trim_start().len();
+        &l[ws.min(indent)..]
+      })
+      .collect();
+    blocks.push(block.join("\n"));
+    rest = &rest[end..];
+  }
+  blocks
+}
+
+/// Merges `entries` into an existing `gamelist.xml` document.
+///
+/// Existing entries whose path is in `owned_paths` (written by rompom, now
+/// updated or removed) are dropped; all others — games scraped by
+/// EmulationStation, folders — are kept as-is, before `entries`.
+pub fn merge_gamelist(existing: &str, owned_paths: &HashSet<String>, entries: &[String]) -> String {
+  let kept: Vec<String> = gamelist_blocks(existing)
+    .into_iter()
+    .filter(|block| {
+      !block.starts_with("<game") || game_path(block).is_none_or(|p| !owned_paths.contains(&p))
+    })
+    .collect();
+  render_gamelist(kept.iter().chain(entries.iter()).map(|e| e.trim()))
+}
+
+/// Writes a complete `gamelist.xml` for the system, aggregating the
+/// `description.xml` of every ROM recorded in state, `orphans` excepted: a ROM
+/// gone from its source stays in state until `--clean`, but is no longer part
+/// of the library.
+///
+/// Entries are the exact ones shipped in the packages, so paths and media
+/// (`./<rom>`, `./data/<romname>/…`) match what batoexec merges on a Batocera
+/// box. They are sorted by ROM filename to keep the file diffable between
+/// runs. Returns the number of games written.
+pub fn write_gamelist(path: &Path, state: &SystemState, orphans: &[Orphan]) -> io::Result<usize> {
+  let orphaned: HashSet<&str> = orphans
+    .iter()
+    .filter_map(|o| o.filename.as_deref())
+    .collect();
+  let mut filenames: Vec<&String> = state
+    .roms
+    .keys()
+    .filter(|f| !orphaned.contains(f.as_str()))
+    .collect();
+  filenames.sort();
+  let entries: Vec<String> = filenames
+    .into_iter()
+    .filter_map(|f| read_game_entry(f))
+    .collect();
+  fs::write(path, render_gamelist(entries.iter().map(String::as_str)))?;
+  Ok(entries.len())
+}
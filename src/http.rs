@@ -0,0 +1,564 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs, io,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use checksums::{hash_file, Algorithm};
+use regex::Regex;
+use reqwest::{
+  blocking::{Client, Response},
+  header::{CONTENT_RANGE, RANGE},
+  StatusCode, Url,
+};
+use serde::Deserialize;
+
+use crate::{
+  conf,
+  package::encode_path_segment,
+  rom::{HttpSource, RomSource, RomSourceData},
+};
+
+/// One file of a manifest.
+#[derive(Deserialize)]
+struct ManifestEntry {
+  /// Path relative to the manifest, `/`-separated.
+  name: String,
+  #[serde(default)]
+  size: Option<u64>,
+  #[serde(default)]
+  sha1: Option<String>,
+  #[serde(default)]
+  md5: Option<String>,
+  #[serde(default)]
+  crc32: Option<String>,
+  /// Download URL, absolute or relative to the manifest. Default: `name`.
+  #[serde(default)]
+  url: Option<String>,
+}
+
+/// A file listed by an HTTP source.
+struct Listed {
+  /// Path relative to the index or manifest.
+  path: String,
+  url: String,
+  size: u64,
+  sha1: Option<String>,
+  md5: Option<String>,
+  crc32: Option<String>,
+}
+
+/// Longest wait for a connection.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest wait for response headers, or for the next chunk of a body.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn client() -> io::Result<Client> {
+  // The blocking client applies `timeout` to each read of a streamed body,
+  // not to the whole transfer: a ROM takes as long as it needs, but a server
+  // that stops sending fails the attempt, and `download` moves on.
+  Client::builder()
+    .connect_timeout(CONNECT_TIMEOUT)
+    .timeout(READ_TIMEOUT)
+    .build()
+    .map_err(io::Error::other)
+}
+
+fn get_text(url: &Url) -> io::Result<String> {
+  client()?
+    .get(url.clone())
+    .send()
+    .and_then(|r| r.error_for_status())
+    .and_then(|r| r.text())
+    .map_err(|e| io::Error::other(format!("{}: {}", url, e)))
+}
+
+fn parse_url(url: &str) -> io::Result<Url> {
+  Url::parse(url).map_err(|e| io::Error::other(format!("{}: {}", url, e)))
+}
+
+/// Decodes `%XX` escapes of a URL path.
+fn decode_path(path: &str) -> String {
+  let bytes = path.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let hex = bytes
+      .get(i + 1..i + 3)
+      .and_then(|h| std::str::from_utf8(h).ok())
+      .and_then(|h| u8::from_str_radix(h, 16).ok());
+    match (bytes[i], hex) {
+      (b'%', Some(b)) => {
+        out.push(b);
+        i += 3;
+      }
+      (b, _) => {
+        out.push(b);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&out).into_owned()
+}
+
+/// `name=sha1` map of a `sha1sum` output (`<sha1>  <name>` or `<sha1> *<name>`).
+fn parse_sha1sums(text: &str) -> HashMap<String, String> {
+  text
+    .lines()
+    .filter_map(|line| {
+      let (sha1, name) = line.trim_end().split_once(' ')?;
+      let name = name.trim_start_matches([' ', '*']).trim_start_matches("./");
+      Some((name.to_string(), sha1.to_lowercase()))
+    })
+    .collect()
+}
+
+/// Files linked from the autoindex page `html` of `base` (nginx, Apache,
+/// `python3 -m http.server`): links to files inside the indexed directory,
+/// subdirectories and parent links excluded. SHA-1s are taken from `sums`.
+fn index_files(base: &Url, html: &str, sums: &HashMap<String, String>) -> Vec<Listed> {
+  let href = Regex::new(r#"(?i)href\s*=\s*"([^"?#]+)""#).unwrap();
+  let mut seen = HashSet::new();
+  let mut files = Vec::new();
+  for link in href.captures_iter(html) {
+    let Ok(target) = base.join(&link[1]) else {
+      continue;
+    };
+    let Some(rest) = target.as_str().strip_prefix(base.as_str()) else {
+      continue;
+    };
+    if rest.is_empty() || rest.ends_with('/') || !seen.insert(rest.to_string()) {
+      continue;
+    }
+    let path = decode_path(rest);
+    files.push(Listed {
+      sha1: sums.get(&path).cloned(),
+      url: target.to_string(),
+      path,
+      size: 0,
+      md5: None,
+      crc32: None,
+    });
+  }
+  files
+}
+
+/// Files linked from an autoindex page, with the SHA-1s of the source's
+/// `sha1sums` file.
+fn list_index(source: &conf::HttpSource, url: &str) -> io::Result<Vec<Listed>> {
+  let base = if url.ends_with('/') {
+    parse_url(url)?
+  } else {
+    parse_url(&format!("{}/", url))?
+  };
+  let html = get_text(&base)?;
+  let sums = match &source.sha1sums {
+    Some(file) => {
+      let sums_url = base.join(file).map_err(io::Error::other)?;
+      parse_sha1sums(&get_text(&sums_url)?)
+    }
+    None => HashMap::new(),
+  };
+  Ok(index_files(&base, &html, &sums))
+}
+
+/// Files of the manifest `text` found at `base`: a YAML or JSON list of
+/// `ManifestEntry`.
+fn manifest_files(base: &Url, text: &str) -> io::Result<Vec<Listed>> {
+  let entries: Vec<ManifestEntry> =
+    serde_yaml::from_str(text).map_err(|e| io::Error::other(format!("{}: {}", base, e)))?;
+  entries
+    .into_iter()
+    .map(|e| {
+      let href = e.url.unwrap_or_else(|| {
+        e.name
+          .split('/')
+          .map(encode_path_segment)
+          .collect::<Vec<_>>()
+          .join("/")
+      });
+      Ok(Listed {
+        url: base.join(&href).map_err(io::Error::other)?.to_string(),
+        path: e.name,
+        size: e.size.unwrap_or(0),
+        sha1: e.sha1.map(|h| h.to_lowercase()),
+        md5: e.md5,
+        crc32: e.crc32,
+      })
+    })
+    .collect()
+}
+
+/// Files of a YAML or JSON manifest.
+fn list_manifest(url: &str) -> io::Result<Vec<Listed>> {
+  let base = parse_url(url)?;
+  manifest_files(&base, &get_text(&base)?)
+}
+
+/// Collects the ROMs of an HTTP source, matching the source patterns against
+/// the path relative to the index or manifest.
+///
+/// Returns the ROMs and the matching files left out because no SHA-1 is known
+/// for them (index pages without `sha1sums`): downloads are always verified.
+pub fn collect(source: &conf::HttpSource) -> io::Result<(Vec<RomSourceData>, Vec<String>)> {
+  let listed = match (&source.url, &source.manifest) {
+    (Some(url), _) => list_index(source, url)?,
+    (None, Some(manifest)) => list_manifest(manifest)?,
+    (None, None) => Vec::new(),
+  };
+
+  let mut roms = Vec::new();
+  let mut unhashed = Vec::new();
+  for file in listed
+    .into_iter()
+    .filter(|f| source.matcher.matches(&f.path))
+  {
+    if file.sha1.is_none() {
+      unhashed.push(file.path);
+      continue;
+    }
+    let filename = file
+      .path
+      .rsplit('/')
+      .next()
+      .unwrap_or(&file.path)
+      .to_string();
+    roms.push(RomSourceData {
+      file_name: file.path,
+      filename,
+      source: RomSource::Http(HttpSource {
+        rom_url: file.url,
+        crc32: file.crc32,
+        md5: file.md5,
+        sha1: file.sha1,
+        size: file.size,
+      }),
+      extra_discs: Vec::new(),
+      romname: String::new(),
+      game_dir: None,
+      origin: source.origin(),
+    });
+  }
+  Ok((roms, unhashed))
+}
+
+/// Checks the SHA-1 of a downloaded file.
+pub fn verify_sha1(path: &Path, sha1: &str) -> io::Result<()> {
+  let found = hash_file(path, Algorithm::SHA1).to_lowercase();
+  if found == sha1.to_lowercase() {
+    Ok(())
+  } else {
+    Err(io::Error::other(format!(
+      "sha1 mismatch for {}: expected {}, got {}",
+      path.display(),
+      sha1,
+      found
+    )))
+  }
+}
+
+/// `<dest>.part`, where a download is written until its SHA-1 is verified.
+fn part_path(dest: &Path) -> PathBuf {
+  let mut part = dest.as_os_str().to_owned();
+  part.push(".part");
+  PathBuf::from(part)
+}
+
+/// First byte of a `Content-Range: bytes <first>-<last>/<total>` header.
+fn range_start(response: &Response) -> Option<u64> {
+  response
+    .headers()
+    .get(CONTENT_RANGE)?
+    .to_str()
+    .ok()?
+    .strip_prefix("bytes ")?
+    .split('-')
+    .next()?
+    .parse()
+    .ok()
+}
+
+/// Appends the rest of `url` to `part`, asking for the bytes it lacks with a
+/// `Range` request. A server ignoring the range sends the whole file, which
+/// replaces `part`.
+fn fetch_part(client: &Client, url: &str, part: &Path) -> io::Result<()> {
+  let offset = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+  let mut request = client.get(url);
+  if offset > 0 {
+    request = request.header(RANGE, format!("bytes={}-", offset));
+  }
+  let response = request.send().map_err(io::Error::other)?;
+  if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+    // Nothing left to fetch: the SHA-1 check decides.
+    return Ok(());
+  }
+  let mut response = response.error_for_status().map_err(io::Error::other)?;
+  let resumed = offset > 0
+    && response.status() == StatusCode::PARTIAL_CONTENT
+    && range_start(&response) == Some(offset);
+  let mut file = if resumed {
+    fs::OpenOptions::new().append(true).open(part)?
+  } else {
+    fs::File::create(part)?
+  };
+  io::copy(&mut response, &mut file)?;
+  Ok(())
+}
+
+/// Downloads a file to `dest` from the first of `urls` (mirrors of the same
+/// file) that works, and checks its SHA-1 when known.
+///
+/// Data goes to `<dest>.part`, renamed to `dest` only once verified. A
+/// dropped connection resumes from the bytes already received, on the next
+/// mirror; a `.part` left by an earlier attempt or run is resumed the same
+/// way. Gives up once every mirror failed twice in a row without progress,
+/// or after as many complete downloads failing the SHA-1 check as mirrors.
+pub fn download(urls: &[String], dest: &Path, sha1: Option<&str>) -> io::Result<()> {
+  let client = client()?;
+  let part = part_path(dest);
+  let size = |path: &Path| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+  // Whether `part` only holds data fetched by this call, not by an earlier run.
+  let mut fresh = !part.exists();
+  let mut attempts = 0;
+  let mut last_error = None;
+  let mut stalled = 0;
+  let mut corrupt = 0;
+  for url in urls.iter().cycle() {
+    if stalled >= 2 * urls.len() || corrupt >= urls.len() {
+      break;
+    }
+    attempts += 1;
+    let before = size(&part);
+    if let Err(e) = fetch_part(&client, url, &part) {
+      last_error = Some(format!("{}: {}", url, e));
+      if size(&part) > before {
+        stalled = 0;
+      } else {
+        stalled += 1;
+      }
+      continue;
+    }
+    match sha1.map_or(Ok(()), |sha1| verify_sha1(&part, sha1)) {
+      Ok(()) => return fs::rename(&part, dest),
+      Err(e) => {
+        // Corrupt or stale data: start over rather than resume it.
+        fs::remove_file(&part).ok();
+        last_error = Some(format!("{}: {}", url, e));
+        if fresh {
+          corrupt += 1;
+        }
+        fresh = true;
+      }
+    }
+  }
+  Err(io::Error::other(match last_error {
+    Some(last) => format!(
+      "download failed after {} attempt(s), last: {}",
+      attempts, last
+    ),
+    None => format!("no URL to download {}", dest.display()),
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    thread,
+  };
+
+  use super::*;
+
+  fn url(s: &str) -> Url {
+    Url::parse(s).unwrap()
+  }
+
+  #[test]
+  fn index_lists_files_of_the_directory_only() {
+    let base = url("http://roms.lan/snes/");
+    let html = r#"
+      <a href="../">../</a>
+      <a href="/">/</a>
+      <a href="?C=N;O=D">Name</a>
+      <a href="Europe/">Europe/</a>
+      <a href="Super%20Mario%20World%20%28USA%29.zip">Super Mario World (USA).zip</a>
+      <A HREF="http://roms.lan/snes/Zelda.zip">Zelda.zip</A>
+      <a href="Zelda.zip">Zelda.zip</a>
+      <a href="http://elsewhere.lan/snes/Other.zip">Other.zip</a>
+      <a href="/other/Game.zip">Game.zip</a>
+    "#;
+    let sums = HashMap::from([("Super Mario World (USA).zip".to_string(), "abc".to_string())]);
+    let files = index_files(&base, html, &sums);
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, ["Super Mario World (USA).zip", "Zelda.zip"]);
+    assert_eq!(
+      files[0].url,
+      "http://roms.lan/snes/Super%20Mario%20World%20%28USA%29.zip"
+    );
+    assert_eq!(files[0].sha1.as_deref(), Some("abc"));
+    assert_eq!(files[1].url, "http://roms.lan/snes/Zelda.zip");
+    assert_eq!(files[1].sha1, None);
+  }
+
+  #[test]
+  fn decode_path_keeps_invalid_escapes() {
+    assert_eq!(decode_path("A%20B%2fC"), "A B/C");
+    assert_eq!(decode_path("100%"), "100%");
+    assert_eq!(decode_path("%zz%4"), "%zz%4");
+  }
+
+  #[test]
+  fn sha1sums_text_binary_and_dot_slash_forms() {
+    let sums = parse_sha1sums(
+      "ABCDEF  Game One.zip\n\
+       012345 *Game Two.zip\n\
+       6789ab  ./Game Three.zip\n\
+       \n",
+    );
+    assert_eq!(sums.len(), 3);
+    assert_eq!(sums["Game One.zip"], "abcdef");
+    assert_eq!(sums["Game Two.zip"], "012345");
+    assert_eq!(sums["Game Three.zip"], "6789ab");
+  }
+
+  #[test]
+  fn manifest_urls_relative_to_the_manifest_or_absolute() {
+    let base = url("http://roms.lan/snes/manifest.yml");
+    let files = manifest_files(
+      &base,
+      r#"
+- name: USA/Game (USA).zip
+  size: 1024
+  sha1: ABCDEF
+- name: Other.zip
+  url: files/other.zip
+- name: Mirror.zip
+  url: https://cdn.example.org/snes/Mirror.zip
+"#,
+    )
+    .unwrap();
+    let urls: Vec<&str> = files.iter().map(|f| f.url.as_str()).collect();
+    assert_eq!(
+      urls,
+      [
+        "http://roms.lan/snes/USA/Game%20%28USA%29.zip",
+        "http://roms.lan/snes/files/other.zip",
+        "https://cdn.example.org/snes/Mirror.zip",
+      ]
+    );
+    assert_eq!(files[0].path, "USA/Game (USA).zip");
+    assert_eq!(files[0].size, 1024);
+    assert_eq!(files[0].sha1.as_deref(), Some("abcdef"));
+  }
+
+  #[test]
+  fn manifest_in_json() {
+    let base = url("http://roms.lan/snes/manifest.json");
+    let files = manifest_files(&base, r#"[{"name": "Game.zip", "sha1": "abc"}]"#).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].url, "http://roms.lan/snes/Game.zip");
+  }
+
+  /// How a test server answers.
+  #[derive(Clone, Copy)]
+  enum Serve {
+    /// Announces the whole file and closes the connection halfway.
+    Truncated,
+    /// Honours `Range` requests.
+    Ranges,
+    /// Ignores `Range` and sends the whole file.
+    Whole,
+  }
+
+  /// Serves `body` on a local port until the test ends. Returns its URL and
+  /// the first byte asked by the `Range` header of each request.
+  fn serve(body: Vec<u8>, mode: Serve) -> (String, Arc<Mutex<Vec<Option<usize>>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/Game.zip", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&requests);
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut range = None;
+        loop {
+          let mut line = String::new();
+          if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+            break;
+          }
+          if let Some(start) = line.to_lowercase().strip_prefix("range: bytes=") {
+            range = start.trim().trim_end_matches('-').parse::<usize>().ok();
+          }
+        }
+        seen.lock().unwrap().push(range);
+        let (head, data) = match (mode, range) {
+          (Serve::Ranges, Some(start)) => (
+            format!(
+              "206 Partial Content\r\nContent-Range: bytes {}-{}/{}",
+              start,
+              body.len() - 1,
+              body.len()
+            ),
+            &body[start..],
+          ),
+          (Serve::Truncated, _) => ("200 OK".to_string(), &body[..body.len() / 2]),
+          _ => ("200 OK".to_string(), &body[..]),
+        };
+        let length = match mode {
+          Serve::Truncated => body.len(),
+          _ => data.len(),
+        };
+        let response = format!(
+          "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+          head, length
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+        stream.write_all(data).ok();
+      }
+    });
+    (url, requests)
+  }
+
+  fn rom() -> Vec<u8> {
+    (0..256 * 1024).map(|i| (i % 251) as u8).collect()
+  }
+
+  fn temp_dest(name: &str) -> PathBuf {
+    let dest = std::env::temp_dir().join(format!("rompom-{}-{}", std::process::id(), name));
+    fs::remove_file(&dest).ok();
+    fs::remove_file(part_path(&dest)).ok();
+    dest
+  }
+
+  #[test]
+  fn dropped_download_resumes_on_the_next_mirror() {
+    let (truncated, first) = serve(rom(), Serve::Truncated);
+    let (ranges, second) = serve(rom(), Serve::Ranges);
+    let dest = temp_dest("resume.zip");
+
+    download(&[truncated, ranges], &dest, None).unwrap();
+
+    assert_eq!(fs::read(&dest).unwrap(), rom());
+    assert!(!part_path(&dest).exists());
+    assert_eq!(*first.lock().unwrap(), [None]);
+    assert_eq!(*second.lock().unwrap(), [Some(rom().len() / 2)]);
+    fs::remove_file(&dest).ok();
+  }
+
+  #[test]
+  fn mirror_ignoring_range_replaces_the_partial_file() {
+    let (truncated, _) = serve(rom(), Serve::Truncated);
+    let (whole, second) = serve(rom(), Serve::Whole);
+    let dest = temp_dest("whole.zip");
+
+    download(&[truncated, whole], &dest, None).unwrap();
+
+    assert_eq!(fs::read(&dest).unwrap(), rom());
+    assert_eq!(*second.lock().unwrap(), [Some(rom().len() / 2)]);
+    fs::remove_file(&dest).ok();
+  }
+}
@@ -0,0 +1,174 @@
+use std::{
+  fs,
+  io::{self, Read},
+  path::PathBuf,
+};
+
+use internet_archive::metadata::Metadata;
+use reqwest::{
+  blocking::Client,
+  header::{ETAG, IF_NONE_MATCH},
+  StatusCode,
+};
+
+use crate::http::{CONNECT_TIMEOUT, READ_TIMEOUT};
+
+const METADATA_API: &str = "https://archive.org/metadata";
+
+/// Item metadata, and the network error when it comes from a cached copy
+/// that could not be revalidated.
+pub struct Loaded {
+  pub metadata: Metadata,
+  pub fallback: Option<String>,
+}
+
+/// `(<item>.json, <item>.etag)` in `<cache dir>/rompom/ia/`.
+fn cache_paths(item: &str) -> Option<(PathBuf, PathBuf)> {
+  let dir = dirs::cache_dir()?.join("rompom").join("ia");
+  Some((
+    dir.join(format!("{}.json", item)),
+    dir.join(format!("{}.etag", item)),
+  ))
+}
+
+fn client() -> io::Result<Client> {
+  // Metadata of large items takes a while to download, but an unreachable
+  // host or a stalled transfer should fail so that the cache is used. The
+  // body is read through `io::Read` (see `fetch`), where the timeout applies
+  // to each read rather than to the whole document.
+  Client::builder()
+    .connect_timeout(CONNECT_TIMEOUT)
+    .timeout(READ_TIMEOUT)
+    .build()
+    .map_err(io::Error::other)
+}
+
+/// `item_last_updated` of a metadata document.
+fn last_updated(json: &str) -> Option<u64> {
+  serde_json::from_str::<serde_json::Value>(json)
+    .ok()?
+    .get("item_last_updated")?
+    .as_u64()
+}
+
+/// Asks the API for `item_last_updated` only, a few bytes even for items
+/// with thousands of files.
+fn remote_last_updated(client: &Client, item: &str) -> io::Result<Option<u64>> {
+  let body = client
+    .get(format!("{}/{}/item_last_updated", METADATA_API, item))
+    .send()
+    .and_then(|r| r.error_for_status())
+    .and_then(|r| r.text())
+    .map_err(io::Error::other)?;
+  Ok(
+    serde_json::from_str::<serde_json::Value>(&body)
+      .ok()
+      .and_then(|v| v.get("result")?.as_u64()),
+  )
+}
+
+/// Downloads the metadata document; `None` when the server answers that the
+/// copy tagged `etag` is still current.
+fn fetch(
+  client: &Client,
+  item: &str,
+  etag: Option<&str>,
+) -> io::Result<Option<(String, Option<String>)>> {
+  let mut request = client.get(format!("{}/{}", METADATA_API, item));
+  if let Some(etag) = etag {
+    request = request.header(IF_NONE_MATCH, etag);
+  }
+  let response = request.send().map_err(io::Error::other)?;
+  if response.status() == StatusCode::NOT_MODIFIED {
+    return Ok(None);
+  }
+  let mut response = response.error_for_status().map_err(io::Error::other)?;
+  let etag = response
+    .headers()
+    .get(ETAG)
+    .and_then(|v| v.to_str().ok())
+    .map(str::to_string);
+  let mut body = String::new();
+  response.read_to_string(&mut body)?;
+  Ok(Some((body, etag)))
+}
+
+fn parse(item: &str, json: &str) -> io::Result<Metadata> {
+  serde_json::from_str(json)
+    .map_err(|e| io::Error::other(format!("invalid metadata for {}: {}", item, e)))
+}
+
+/// Loads the metadata of an Internet Archive item through the disk cache.
+///
+/// A cached copy is used as long as its `item_last_updated` matches the
+/// item's (or the server confirms its ETag); `refresh` skips that check and
+/// downloads the document again. When the network fails or the document
+/// downloaded does not parse, the cached copy is used and the error returned
+/// in `Loaded::fallback`; without a cached copy the error is returned.
+pub fn load(item: &str, refresh: bool) -> io::Result<Loaded> {
+  let paths = cache_paths(item);
+  // A copy that does not parse is ignored, and so is its ETag: a 304 would
+  // leave nothing to use.
+  let cached = paths
+    .as_ref()
+    .and_then(|(json, _)| fs::read_to_string(json).ok())
+    .and_then(|json| Some((parse(item, &json).ok()?, json)));
+  let etag = paths
+    .as_ref()
+    .filter(|_| cached.is_some() && !refresh)
+    .and_then(|(_, etag)| fs::read_to_string(etag).ok());
+
+  let fetched = client().and_then(|client| {
+    if let Some(local) = cached
+      .as_ref()
+      .and_then(|(_, json)| last_updated(json))
+      .filter(|_| !refresh)
+    {
+      if remote_last_updated(&client, item)? == Some(local) {
+        return Ok(None);
+      }
+    }
+    fetch(&client, item, etag.as_deref())
+  });
+
+  match (fetched, cached) {
+    (Ok(Some((json, etag))), cached) => {
+      let metadata = match (parse(item, &json), cached) {
+        (Ok(metadata), _) => metadata,
+        // A broken response does not replace a valid cached copy.
+        (Err(e), Some((metadata, _))) => {
+          return Ok(Loaded {
+            metadata,
+            fallback: Some(e.to_string()),
+          })
+        }
+        (Err(e), None) => return Err(e),
+      };
+      if let Some((json_path, etag_path)) = paths {
+        // The cache is an optimisation: a failed write only costs a download.
+        if let Some(dir) = json_path.parent() {
+          fs::create_dir_all(dir).ok();
+        }
+        fs::write(&json_path, &json).ok();
+        match etag {
+          Some(etag) => fs::write(&etag_path, etag).ok(),
+          None => fs::remove_file(&etag_path).ok(),
+        };
+      }
+      Ok(Loaded {
+        metadata,
+        fallback: None,
+      })
+    }
+    (Ok(None), Some((metadata, _))) => Ok(Loaded {
+      metadata,
+      fallback: None,
+    }),
+    (Err(e), Some((metadata, _))) => Ok(Loaded {
+      metadata,
+      fallback: Some(e.to_string()),
+    }),
+    (Ok(None), None) => Err(io::Error::other(format!("no metadata for {}", item))),
+    (Err(e), None) => Err(e),
+  }
+}
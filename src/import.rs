@@ -0,0 +1,128 @@
+use std::{collections::HashMap, fs, io, path::Path, time::UNIX_EPOCH};
+
+use checksums::{hash_file, Algorithm};
+
+use crate::{
+  conf::FileFilter, disc_indicator, frontend::link_or_copy, gamelist, state::RomStateEntry,
+  state::SystemState,
+};
+
+/// Outcome of `--import`.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+  /// ROMs added to state.
+  pub imported: usize,
+  /// Among them, ROMs whose ScreenScraper game id came from the gamelist.
+  pub with_game_id: usize,
+  /// ROMs that already had a state entry (left untouched).
+  pub already_known: usize,
+  /// Multi-disc files, not imported (their state key is the grouped name).
+  pub skipped: Vec<String>,
+}
+
+/// Reads the ScreenScraper game id of every gamelist entry, by ROM filename.
+///
+/// Batocera writes `<game id="…" source="ScreenScraper.fr">` when it scraped
+/// the game itself; entries from other scrapers are ignored. The other fields
+/// of the entry are not imported: the package metadata is fetched again from
+/// ScreenScraper by id.
+fn gamelist_ids(xml: &str) -> HashMap<String, String> {
+  gamelist::gamelist_blocks(xml)
+    .into_iter()
+    .filter(|block| block.starts_with("<game"))
+    .filter_map(|block| {
+      let tag = &block[..block.find('>')?];
+      if attribute(tag, "source").is_some_and(|s| !s.to_lowercase().contains("screenscraper")) {
+        return None;
+      }
+      let id = attribute(tag, "id").filter(|id| id.parse::<u32>().is_ok_and(|n| n > 0))?;
+      let path = gamelist::game_path(&block)?;
+      Some((path.strip_prefix("./").unwrap_or(&path).to_string(), id))
+    })
+    .collect()
+}
+
+/// Value of `name="…"` in an opening tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+  let pattern = format!(" {}=\"", name);
+  let start = tag.find(&pattern)? + pattern.len();
+  let end = start + tag[start..].find('"')?;
+  Some(tag[start..end].to_string())
+}
+
+/// Seeds `state` from an existing Batocera ROM directory (`userdata/roms/<dir>`).
+///
+/// Every file matching one of the source `filters` is hashed and recorded under its filename,
+/// with the ScreenScraper game id found in `gamelist.xml`, and hard-linked
+/// (or copied) into its output directory. The next run then finds the ROM in
+/// place, looks the game up by id instead of identifying it again, and only
+/// builds the package and fetches the media.
+///
+/// Existing state entries are never overwritten. Multi-disc files are
+/// skipped: rompom keys them by their grouped name and re-identifies them.
+pub fn import_library(
+  dir: &Path,
+  filters: &[FileFilter],
+  state: &mut SystemState,
+) -> io::Result<ImportReport> {
+  let ids = fs::read_to_string(dir.join("gamelist.xml"))
+    .map(|xml| gamelist_ids(&xml))
+    .unwrap_or_default();
+  let mut filenames: Vec<String> = fs::read_dir(dir)?
+    .filter_map(|e| e.ok())
+    .filter(|e| e.path().is_file())
+    .filter_map(|e| e.file_name().into_string().ok())
+    .filter(|name| filters.iter().any(|f| f.matches(name)))
+    .collect();
+  filenames.sort();
+
+  let mut report = ImportReport::default();
+  for filename in filenames {
+    let stem = Path::new(&filename)
+      .file_stem()
+      .and_then(|s| s.to_str())
+      .unwrap_or(&filename);
+    if disc_indicator(stem).is_some() {
+      report.skipped.push(filename);
+      continue;
+    }
+    if state.roms.contains_key(&filename) {
+      report.already_known += 1;
+      continue;
+    }
+
+    let path = dir.join(&filename);
+    let meta = fs::metadata(&path)?;
+    let mtime = meta
+      .modified()
+      .ok()
+      .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+      .map(|d| d.as_secs())
+      .unwrap_or(0);
+
+    let dest = Path::new(&filename).with_extension("").join(&filename);
+    if !dest.exists() {
+      link_or_copy(&path, &dest)?;
+    }
+
+    let ss_game_id = ids.get(&filename).cloned();
+    if ss_game_id.is_some() {
+      report.with_game_id += 1;
+    }
+    state.insert(
+      filename,
+      RomStateEntry {
+        ss_game_id,
+        rom_sha1: hash_file(&path, Algorithm::SHA1).to_lowercase(),
+        rom_mtime: mtime,
+        rom_size: meta.len(),
+        rom_crc32: Some(hash_file(&path, Algorithm::CRC32).to_lowercase()),
+        rom_md5: Some(hash_file(&path, Algorithm::MD5).to_lowercase()),
+        ..Default::default()
+      },
+    );
+    report.imported += 1;
+  }
+
+  Ok(report)
+}
@@ -1,18 +1,37 @@
+mod bios;
+mod collect;
 mod conf;
+mod dat;
+mod duplicates;
 mod emulationstation;
+mod esde;
+mod fingerprint;
+mod folder;
+mod frontend;
+mod gamelist;
+mod http;
+mod ia_cache;
+mod import;
+mod naming;
+mod orphans;
 mod package;
+mod pegasus;
 mod queue;
+mod rename;
+mod retroarch;
 mod rom;
+mod selection;
 mod state;
 mod summary;
 mod ui;
+mod userdata;
 mod worker;
 
 use std::{
   collections::HashMap,
   env, fs,
   io::{self, Write as _},
-  path::Path,
+  path::{Path, PathBuf},
   sync::{
     atomic::{AtomicBool, AtomicUsize, Ordering},
     Arc, Mutex,
@@ -20,15 +39,11 @@ use std::{
   thread,
 };
 
-use glob::Pattern;
-use internet_archive::metadata::Metadata;
 use screenscraper::ScreenScraper;
 
-use crate::conf::{Conf, Source};
+use crate::conf::{Conf, FileFilter, Source};
 use crate::queue::{Semaphore, TaskQueue};
-use crate::rom::{
-  DiscFile, FolderSource, IaSource, Rom, RomSource, RomSourceData, StepKind, StepStatus,
-};
+use crate::rom::{DiscFile, Rom, RomSource, RomSourceData, StepKind, StepStatus};
 use crate::state::SystemState;
 use crate::ui::Ui;
 use crate::worker::WorkerContext;
@@ -51,7 +66,7 @@ const N_BLOCKING_WORKERS: usize = 2;
 /// Examples:
 /// - `"Enemy Zero (USA) (Disc 0)"` → `Some(("Enemy Zero (USA)", 0))`
 /// - `"Panzer Dragoon Saga (Disc 1)"` → `Some(("Panzer Dragoon Saga", 1))`
-fn disc_indicator(stem: &str) -> Option<(String, u32)> {
+pub(crate) fn disc_indicator(stem: &str) -> Option<(String, u32)> {
   let mut result: Option<(String, u32)> = None;
   let mut search_from = 0;
 
@@ -121,7 +136,9 @@ fn group_multi_disc(sources: Vec<RomSourceData>) -> Vec<RomSourceData> {
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_string();
-      match disc_indicator(&stem) {
+      // Game folders already hold all their files.
+      let indicator = disc_indicator(&stem).filter(|_| src.game_dir.is_none());
+      match indicator {
         Some((base, num)) => Parsed {
           source: src,
           base,
@@ -203,10 +220,20 @@ fn group_multi_disc(sources: Vec<RomSourceData>) -> Vec<RomSourceData> {
             size: ia.size,
             local_path: None,
           },
+          RomSource::Http(http) => DiscFile {
+            file_name: src.file_name.clone(),
+            filename: src.filename.clone(),
+            rom_url: http.rom_url.clone(),
+            sha1: http.sha1.clone(),
+            md5: http.md5.clone(),
+            crc32: http.crc32.clone(),
+            size: http.size,
+            local_path: None,
+          },
           RomSource::Folder(f) => DiscFile {
             file_name: src.file_name.clone(),
             filename: src.filename.clone(),
-            rom_url: String::new(),
+            rom_url: folder::mirror_url(f.mirror.as_deref(), &f.rel_path),
             sha1: None,
             md5: None,
             crc32: None,
@@ -228,6 +255,40 @@ fn group_multi_disc(sources: Vec<RomSourceData>) -> Vec<RomSourceData> {
   result
 }
 
+/// Writes `<system>.gamelist.xml` from the packaged ROMs recorded in state.
+/// Returns `(path, games written)`, or `None` after printing a warning.
+fn export_gamelist(
+  system_name: &str,
+  state: &SystemState,
+  orphans: &[orphans::Orphan],
+) -> Option<(String, usize)> {
+  let path = format!("{}.gamelist.xml", system_name);
+  match gamelist::write_gamelist(Path::new(&path), state, orphans) {
+    Ok(count) => Some((path, count)),
+    Err(e) => {
+      eprintln!("Warning: could not write {}: {}", path, e);
+      None
+    }
+  }
+}
+
+/// Installs the packaged ROMs into `<root>/<dir>/` (see `userdata::export`).
+/// Returns `(system directory, report)`, or `None` after printing a warning.
+fn export_userdata(
+  root: &Path,
+  system: &conf::System,
+  state: &SystemState,
+) -> Option<(PathBuf, userdata::UserdataReport)> {
+  let dir = root.join(&system.dir);
+  match userdata::export(root, system, state) {
+    Ok(report) => Some((dir, report)),
+    Err(e) => {
+      eprintln!("Warning: could not export to {}: {}", dir.display(), e);
+      None
+    }
+  }
+}
+
 fn print_usage(program: &str, opts: getopts::Options) {
   let brief = format!("Usage: {} -s SYSTEM", program);
   print!("{}", opts.usage(&brief));
@@ -259,6 +320,44 @@ fn main() {
     "debug",
     "write <system>.debug.log with per-ROM pipeline decisions (useful to diagnose false updates)",
   );
+  opts.optopt(
+    "",
+    "clean",
+    "archive or delete state entries and output dirs of ROMs no longer in the source, then exit",
+    "archive|delete",
+  );
+  opts.optflag(
+    "",
+    "gamelist",
+    "write <system>.gamelist.xml aggregating the description.xml of every packaged ROM",
+  );
+  opts.optopt(
+    "",
+    "export",
+    "install ROMs, media and a merged gamelist.xml into DIR/<dir> (a Batocera userdata/roms tree), without pacman",
+    "DIR",
+  );
+  opts.optopt(
+    "",
+    "import",
+    "seed <system>.state.yml from an existing library in DIR/<dir> (a Batocera userdata/roms tree), then exit",
+    "DIR",
+  );
+  opts.optflag(
+    "",
+    "bios",
+    "verify the system's BIOS files and write the PKGBUILD of its bios package, then exit",
+  );
+  opts.optflag(
+    "",
+    "refresh-metadata",
+    "download Internet Archive item metadata again instead of revalidating the cached copy",
+  );
+  opts.optflag(
+    "",
+    "force",
+    "run the whole pipeline even when sources, configuration and templates are unchanged since the last run",
+  );
   opts.optflag("h", "help", "print this help menu");
 
   let matches = match opts.parse(&args[1..]) {
@@ -323,24 +422,121 @@ fn main() {
     None
   };
 
+  let export_gamelist_flag = matches.opt_present("gamelist");
+  let refresh_metadata = matches.opt_present("refresh-metadata");
+  let force = matches.opt_present("force");
+  let userdata_root = matches.opt_str("export").map(PathBuf::from);
+
+  let clean_mode = match matches.opt_str("clean") {
+    Some(m) => match orphans::CleanMode::parse(&m) {
+      Some(mode) => Some(mode),
+      None => {
+        eprintln!("Invalid --clean mode '{}': expected archive or delete", m);
+        std::process::exit(1);
+      }
+    },
+    None => None,
+  };
+
   let system = match conf.find_system(&system_name) {
-    Some(s) => s,
+    Some(Ok(s)) => s,
+    Some(Err(e)) => {
+      eprintln!("Error: {}", e);
+      std::process::exit(1);
+    }
     None => {
       eprintln!("System '{}' not found in rompom.yml", system_name);
       return;
     }
   };
 
-  let source = match system.source.clone() {
-    Some(s) => s,
-    None => {
-      eprintln!(
-        "System '{}' has no source configured in rompom.yml",
-        system_name
-      );
-      return;
+  // ── BIOS package ──────────────────────────────────────────────────────
+
+  if matches.opt_present("bios") {
+    let Some(bios) = &system.bios else {
+      eprintln!("System '{}' has no bios section in rompom.yml", system_name);
+      std::process::exit(1);
+    };
+    let report = match bios::build_package(&system.name, bios, refresh_metadata) {
+      Ok(r) => r,
+      Err(e) => {
+        eprintln!("Error: bios package {} failed: {}", bios.package, e);
+        std::process::exit(1);
+      }
+    };
+    for check in &report.checks {
+      match &check.status {
+        bios::BiosStatus::Ok => println!("  ok        {}", check.path),
+        bios::BiosStatus::Missing => println!("  missing   {}", check.path),
+        bios::BiosStatus::Mismatch(algorithm, expected, found) => println!(
+          "  mismatch  {} ({} expected {}, found {})",
+          check.path, algorithm, expected, found
+        ),
+      }
     }
-  };
+    let verified = report
+      .checks
+      .iter()
+      .filter(|c| matches!(c.status, bios::BiosStatus::Ok))
+      .count();
+    println!(
+      "{}/{} BIOS file(s) verified → {}/PKGBUILD (pkgver {})",
+      verified,
+      report.checks.len(),
+      report.directory.display(),
+      report.pkgver
+    );
+    if !report.complete() {
+      std::process::exit(1);
+    }
+    return;
+  }
+
+  if system.sources.is_empty() {
+    eprintln!(
+      "System '{}' has no source configured in rompom.yml",
+      system_name
+    );
+    return;
+  }
+
+  // ── Import ────────────────────────────────────────────────────────────
+
+  if let Some(root) = matches.opt_str("import") {
+    let dir = Path::new(&root).join(&system.dir);
+    let filters: Vec<FileFilter> = system
+      .sources
+      .iter()
+      .flat_map(|source| match source {
+        Source::InternetArchive(items) => items.iter().map(|i| i.matcher.clone()).collect(),
+        Source::Folder(f) => vec![f.matcher.clone()],
+        Source::Http(h) => vec![h.matcher.clone()],
+      })
+      .collect();
+    let state_path = format!("{}.state.yml", system_name);
+    let mut state = SystemState::load(&state_path);
+    let report = match import::import_library(&dir, &filters, &mut state) {
+      Ok(r) => r,
+      Err(e) => {
+        eprintln!("Error: import from {} failed: {}", dir.display(), e);
+        std::process::exit(1);
+      }
+    };
+    if let Err(e) = state.save_with_rotation(&state_path) {
+      eprintln!("Warning: could not save state: {}", e);
+    }
+    for filename in &report.skipped {
+      println!("  skipped (multi-disc)  {}", filename);
+    }
+    println!(
+      "{} ROM(s) imported from {} ({} with a ScreenScraper game id), {} already in state",
+      report.imported,
+      dir.display(),
+      report.with_game_id,
+      report.already_known
+    );
+    return;
+  }
 
   // ── Resume check ──────────────────────────────────────────────────────
 
@@ -378,6 +574,17 @@ fn main() {
     None
   };
 
+  let dat = match &system.dat {
+    Some(path) => match dat::Dat::load(path) {
+      Ok(d) => Some(d),
+      Err(e) => {
+        eprintln!("Error: could not load DAT {}: {}", path.display(), e);
+        std::process::exit(1);
+      }
+    },
+    None => None,
+  };
+
   // ── Collection ────────────────────────────────────────────────────────
   //
   // Collect RomSourceData for all matching files first (total unknown),
@@ -386,84 +593,166 @@ fn main() {
   let interrupted = Arc::new(AtomicBool::new(false));
   let queue = TaskQueue::new();
   let ui = Ui::new(Arc::clone(&interrupted), Arc::clone(&queue));
-  let mut sources: Vec<RomSourceData> = Vec::new();
-
-  match &source {
-    Source::InternetArchive(ia_items) => {
-      for item in ia_items {
-        ui.fetching_metadata(&item.item);
-        let metadata = Arc::new(Metadata::get(&item.item).unwrap());
-
-        for file in metadata.files.iter().filter(|f| {
-          let filename = Path::new(&f.name).file_name().unwrap().to_str().unwrap();
-          item
-            .filter
-            .iter()
-            .any(|pat| Pattern::new(pat).unwrap().matches(filename))
-        }) {
-          let filename = Path::new(&file.name)
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-          let rom_url = metadata
-            .file_urls(&file.name)
-            .unwrap()
-            .into_iter()
-            .next()
-            .unwrap_or_default();
-          sources.push(RomSourceData {
-            file_name: file.name.clone(),
-            filename,
-            source: RomSource::InternetArchive(IaSource {
-              rom_url,
-              crc32: file.crc32.clone(),
-              md5: file.md5.clone(),
-              sha1: file.sha1.clone(),
-              size: file
-                .size
-                .as_deref()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(0),
-              metadata: Arc::clone(&metadata),
-            }),
-            extra_discs: Vec::new(),
-          });
-        }
-      }
+  let collect::Collected {
+    mut sources,
+    unhashed,
+    stale_metadata,
+    errors: collection_errors,
+    skipped,
+    failed_origins,
+  } = collect::collect(&system, refresh_metadata, &ui);
+  if sources.is_empty() && !collection_errors.is_empty() {
+    drop(ui);
+    for e in &collection_errors {
+      eprintln!("Error: {}", e);
     }
+    std::process::exit(1);
+  }
 
-    Source::Folder(folder) => {
-      ui.fetching_metadata(&folder.path);
-      let dir = Path::new(&folder.path);
-      for entry in fs::read_dir(dir).unwrap() {
-        let entry = entry.unwrap();
-        let path = entry.path();
-        if !path.is_file() {
-          continue;
-        }
-        let filename = path.file_name().unwrap().to_str().unwrap().to_string();
-        if !folder
-          .filter
-          .iter()
-          .any(|pat| Pattern::new(pat).unwrap().matches(&filename))
-        {
-          continue;
-        }
-        sources.push(RomSourceData {
-          file_name: path.to_str().unwrap().to_string(),
-          filename,
-          source: RomSource::Folder(FolderSource { local_path: path }),
-          extra_discs: Vec::new(),
-        });
+  // ── Duplicates across sources ─────────────────────────────────────────
+
+  let state_path = format!("{}.state.yml", system_name);
+  let mut state = SystemState::load(&state_path);
+  let duplicates = duplicates::resolve(&mut sources, system.duplicates, dat.as_ref(), &state);
+  let duplicates_report = (!duplicates.is_empty()).then(|| {
+    let path = format!("{}.duplicates.txt", system_name);
+    if let Err(e) = duplicates::write_report(&path, &duplicates) {
+      eprintln!("Warning: could not write {}: {}", path, e);
+    }
+    (path, duplicates)
+  });
+
+  // ── DAT filtering ─────────────────────────────────────────────────────
+
+  let dat_report = dat.as_ref().map(|dat| {
+    let report = dat::filter_sources(&mut sources, dat, &state);
+    let path = format!("{}.dat.txt", system_name);
+    if let Err(e) = dat::write_report(&path, &report) {
+      eprintln!("Warning: could not write {}: {}", path, e);
+    }
+    (path, report)
+  });
+
+  // ── 1G1R selection ────────────────────────────────────────────────────
+
+  let selection_report = system.one_game_one_rom.as_ref().map(|conf| {
+    let report = selection::select(&mut sources, conf, dat.as_ref());
+    let path = format!("{}.1g1r.txt", system_name);
+    if let Err(e) = selection::write_report(&path, &report) {
+      eprintln!("Warning: could not write {}: {}", path, e);
+    }
+    (path, report)
+  });
+
+  // ── Group multi-disc files ────────────────────────────────────────────
+
+  let mut sources = group_multi_disc(sources);
+
+  // ── Upstream renames + package names ──────────────────────────────────
+
+  let renames = rename::migrate_renames(&sources, &mut state);
+  let origin_changes = duplicates::origin_changes(&sources, &state);
+  let name_collisions = naming::resolve_romnames(&mut sources, &state, system.collision);
+  rename::record_replaces(&renames, &sources, &mut state);
+  if !renames.is_empty() {
+    // Directories and state move together, whatever path the run takes next.
+    rename::move_output_dirs(&renames, &sources);
+    if let Err(e) = state.save_with_rotation(&state_path) {
+      eprintln!("Warning: could not save state: {}", e);
+    }
+  }
+
+  // ── Orphans ───────────────────────────────────────────────────────────
+
+  // ROMs of a source that could not be read are missing, not gone.
+  let mut orphans = orphans::find_orphans(&sources, &state, &system);
+  orphans.retain(|o| {
+    o.filename
+      .as_ref()
+      .and_then(|f| state.roms.get(f))
+      .and_then(|e| e.origin.as_ref())
+      .is_none_or(|origin| !failed_origins.contains(origin))
+  });
+  let orphans_path = format!("{}.orphans.txt", system_name);
+  if let Err(e) = orphans::write_pkgname_list(&orphans_path, &orphans) {
+    eprintln!("Warning: could not write {}: {}", orphans_path, e);
+  }
+
+  if let Some(mode) = clean_mode {
+    drop(ui);
+    if !failed_origins.is_empty() {
+      for e in &collection_errors {
+        eprintln!("Error: {}", e);
       }
+      eprintln!("Error: not cleaning while some sources could not be collected");
+      std::process::exit(1);
+    }
+    let archive_dir = format!("{}.archive", system_name);
+    if let Err(e) = orphans::clean(&orphans, &mut state, mode, Path::new(&archive_dir)) {
+      eprintln!("Error: clean failed: {}", e);
+      std::process::exit(1);
     }
+    if let Err(e) = state.save_with_rotation(&state_path) {
+      eprintln!("Warning: could not save state: {}", e);
+    }
+    for orphan in &orphans {
+      println!("  {}", orphan.pkgname);
+    }
+    match mode {
+      orphans::CleanMode::Archive => println!(
+        "{} orphan(s) archived to {}/ — drop them from the repo with the list in {}",
+        orphans.len(),
+        archive_dir,
+        orphans_path
+      ),
+      orphans::CleanMode::Delete => println!(
+        "{} orphan(s) deleted — drop them from the repo with the list in {}",
+        orphans.len(),
+        orphans_path
+      ),
+    }
+    return;
   }
 
-  // ── Group multi-disc files ────────────────────────────────────────────
+  // ── Unchanged system ──────────────────────────────────────────────────
+  //
+  // Same sources, configuration and templates as the last successful run:
+  // every PKGBUILD is already up to date, skip ScreenScraper entirely.
 
-  let sources = group_multi_disc(sources);
+  let fingerprint = fingerprint::compute(&sources, &system, &conf.lang);
+  if !force && resumed_state.is_none() && fingerprint::unchanged(&fingerprint, &sources, &state) {
+    let gamelist = if export_gamelist_flag {
+      export_gamelist(&system_name, &state, &orphans)
+    } else {
+      None
+    };
+    let exports = frontend::run_exports(&system, &state);
+    let userdata = userdata_root
+      .as_deref()
+      .and_then(|root| export_userdata(root, &system, &state));
+    let mut summary = ui.summary();
+    summary.total = sources.len();
+    summary.success = sources.len();
+    summary.unchanged = sources.len();
+    summary.fingerprint_unchanged = true;
+    summary.gamelist = gamelist;
+    summary.exports = exports;
+    summary.userdata = userdata;
+    summary.name_collisions = name_collisions;
+    summary.renames = renames;
+    summary.orphans = orphans;
+    summary.dat = dat_report;
+    summary.selection = selection_report;
+    summary.duplicates = duplicates_report;
+    summary.origin_changes = origin_changes;
+    summary.unhashed = unhashed;
+    summary.stale_metadata = stale_metadata;
+    summary.collection_errors = collection_errors;
+    summary.skipped = skipped;
+    drop(ui);
+    summary.print();
+    return;
+  }
 
   // ── RomSourceData → Arc<Mutex<Rom>> ──────────────────────────────────
 
@@ -510,8 +799,7 @@ fn main() {
 
   let n_disc = ss.user_info.maxthreads as usize;
   let modal_tx = ui.modal_sender();
-  let state_path = format!("{}.state.yml", system_name);
-  let state = Arc::new(Mutex::new(SystemState::load(&state_path)));
+  let state = Arc::new(Mutex::new(state));
   let ss = Arc::new(ss);
   let system = Arc::new(system);
   let lang = Arc::new(conf.lang);
@@ -548,7 +836,30 @@ fn main() {
   // All ROMs already done (full resume with no pending work).
   if remaining_count == 0 {
     fs::remove_file(&run_path).ok();
-    let summary = ui.summary();
+    let gamelist = if export_gamelist_flag {
+      export_gamelist(&system_name, &state.lock().unwrap(), &orphans)
+    } else {
+      None
+    };
+    let exports = frontend::run_exports(&system, &state.lock().unwrap());
+    let userdata = userdata_root
+      .as_deref()
+      .and_then(|root| export_userdata(root, &system, &state.lock().unwrap()));
+    let mut summary = ui.summary();
+    summary.gamelist = gamelist;
+    summary.exports = exports;
+    summary.userdata = userdata;
+    summary.name_collisions = name_collisions;
+    summary.renames = renames;
+    summary.orphans = orphans;
+    summary.dat = dat_report;
+    summary.selection = selection_report;
+    summary.duplicates = duplicates_report;
+    summary.origin_changes = origin_changes;
+    summary.unhashed = unhashed;
+    summary.stale_metadata = stale_metadata;
+    summary.collection_errors = collection_errors;
+    summary.skipped = skipped;
     drop(ui);
     summary.print();
     return;
@@ -606,6 +917,18 @@ fn main() {
 
   // ── Post-join ─────────────────────────────────────────────────────────
 
+  // Only a complete run without errors may let the next one stop early.
+  let failed = all_roms.iter().any(|rom_arc| {
+    let rom = rom_arc.lock().unwrap();
+    rom
+      .pipeline
+      .iter()
+      .any(|step| matches!(step.status, StepStatus::Failed(_)))
+  });
+  state.lock().unwrap().fingerprint =
+    (!failed && failed_origins.is_empty() && !interrupted.load(Ordering::SeqCst))
+      .then_some(fingerprint);
+
   // Flush accumulated ROM state to disk (partial on interrupt, complete otherwise).
   if let Err(e) = state.lock().unwrap().save_with_rotation(&state_path) {
     eprintln!("Warning: could not save state: {}", e);
@@ -627,6 +950,16 @@ fn main() {
   // Clean up leftover run file from a previous interrupted run.
   fs::remove_file(&run_path).ok();
 
+  let gamelist = if export_gamelist_flag {
+    export_gamelist(&system_name, &state.lock().unwrap(), &orphans)
+  } else {
+    None
+  };
+  let exports = frontend::run_exports(&system, &state.lock().unwrap());
+  let userdata = userdata_root
+    .as_deref()
+    .and_then(|root| export_userdata(root, &system, &state.lock().unwrap()));
+
   // ── Step telemetry ─────────────────────────────────────────────────
 
   let mut duration_buckets: HashMap<StepKind, Vec<std::time::Duration>> = HashMap::new();
@@ -666,6 +999,20 @@ fn main() {
 
   let mut summary = ui.summary();
   summary.step_avg_durations = step_avg_durations;
+  summary.name_collisions = name_collisions;
+  summary.renames = renames;
+  summary.orphans = orphans;
+  summary.gamelist = gamelist;
+  summary.exports = exports;
+  summary.userdata = userdata;
+  summary.dat = dat_report;
+  summary.selection = selection_report;
+  summary.duplicates = duplicates_report;
+  summary.origin_changes = origin_changes;
+  summary.unhashed = unhashed;
+  summary.stale_metadata = stale_metadata;
+  summary.collection_errors = collection_errors;
+  summary.skipped = skipped;
   drop(ui);
   summary.print();
 }
@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+
+use crate::{
+  conf::CollisionStrategy,
+  package::normalize_name,
+  rom::{HttpSource, IaSource, RomSource, RomSourceData},
+  state::SystemState,
+};
+
+/// One package name collision resolved by `resolve_romnames`.
+pub struct NameCollision {
+  /// Logical filename of the ROM that received a suffixed name.
+  pub filename: String,
+  /// Normalised name it would have had without disambiguation.
+  pub base: String,
+  /// Name actually assigned.
+  pub romname: String,
+}
+
+/// Assigns a unique `romname` to every collected source.
+///
+/// `normalize_name` strips punctuation and spaces, so different ROMs can map
+/// to the same package name (e.g. `"Foo (USA).zip"` and `"Foo, USA.zip"`).
+/// Resolution is deterministic and stable across runs:
+///
+/// 1. Names persisted in state are kept as long as they remain unique.
+/// 2. ROMs already packaged in a previous run keep priority on the bare
+///    normalised name over newly discovered ROMs.
+/// 3. Remaining ROMs are processed in filename order; the first one gets the
+///    bare name, the others a suffix chosen by `strategy`.
+///
+/// Names persisted for state entries that are not part of this run are
+/// reserved too, so a new ROM never takes over an existing package.
+///
+/// Returns the list of collisions that required a suffix.
+pub fn resolve_romnames(
+  sources: &mut [RomSourceData],
+  state: &SystemState,
+  strategy: CollisionStrategy,
+) -> Vec<NameCollision> {
+  let current: HashSet<&str> = sources.iter().map(|s| s.filename.as_str()).collect();
+  let mut taken: HashSet<String> = state
+    .roms
+    .iter()
+    .filter(|(filename, _)| !current.contains(filename.as_str()))
+    .map(|(filename, entry)| {
+      entry
+        .romname
+        .clone()
+        .unwrap_or_else(|| normalize_name(filename))
+    })
+    .collect();
+
+  // Persisted names first, then previously packaged ROMs, then new ones.
+  let mut order: Vec<usize> = (0..sources.len()).collect();
+  order.sort_by_key(|&i| {
+    let rank = match state.roms.get(&sources[i].filename) {
+      Some(entry) if entry.romname.is_some() => 0,
+      Some(_) => 1,
+      None => 2,
+    };
+    (rank, sources[i].filename.clone())
+  });
+
+  let mut collisions = Vec::new();
+
+  for i in order {
+    let source = &mut sources[i];
+    let persisted = state
+      .roms
+      .get(&source.filename)
+      .and_then(|e| e.romname.clone())
+      .filter(|name| !taken.contains(name));
+    let base = normalize_name(&source.filename);
+
+    let romname = match persisted {
+      Some(name) => name,
+      None if !taken.contains(&base) => base.clone(),
+      None => {
+        let sha1 = match &source.source {
+          RomSource::InternetArchive(IaSource { sha1, .. })
+          | RomSource::Http(HttpSource { sha1, .. }) => sha1.as_deref(),
+          RomSource::Folder(_) => None,
+        };
+        let name = disambiguate(&base, sha1, strategy, &taken);
+        collisions.push(NameCollision {
+          filename: source.filename.clone(),
+          base: base.clone(),
+          romname: name.clone(),
+        });
+        name
+      }
+    };
+
+    taken.insert(romname.clone());
+    source.romname = romname;
+  }
+
+  collisions.sort_by(|a, b| a.filename.cmp(&b.filename));
+  collisions
+}
+
+/// Builds a suffixed name for `base` that is not in `taken`.
+///
+/// `Sha1` appends the first 8 hex digits of the ROM sha1 when known; it falls
+/// back to the numeric scheme when the sha1 is unknown at collection time
+/// (folder sources) or when the sha1 suffix itself is already taken.
+fn disambiguate(
+  base: &str,
+  sha1: Option<&str>,
+  strategy: CollisionStrategy,
+  taken: &HashSet<String>,
+) -> String {
+  if strategy == CollisionStrategy::Sha1 {
+    if let Some(sha1) = sha1.filter(|s| s.len() >= 8) {
+      let name = format!("{}-{}", base, sha1[..8].to_lowercase());
+      if !taken.contains(&name) {
+        return name;
+      }
+    }
+  }
+
+  (2..)
+    .map(|n| format!("{}-{}", base, n))
+    .find(|name| !taken.contains(name))
+    .expect("unbounded suffix range")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::state::RomStateEntry;
+
+  fn http(filename: &str, sha1: Option<&str>) -> RomSourceData {
+    RomSourceData {
+      file_name: filename.to_string(),
+      filename: filename.to_string(),
+      source: RomSource::Http(HttpSource {
+        rom_url: format!("http://roms.lan/{}", filename),
+        crc32: None,
+        md5: None,
+        sha1: sha1.map(str::to_string),
+        size: 0,
+      }),
+      extra_discs: Vec::new(),
+      romname: String::new(),
+      game_dir: None,
+      origin: String::new(),
+    }
+  }
+
+  fn romnames(sources: &[RomSourceData]) -> Vec<&str> {
+    sources.iter().map(|s| s.romname.as_str()).collect()
+  }
+
+  #[test]
+  fn existing_rom_keeps_the_bare_name_over_a_new_one() {
+    // "Foo (USA).zip" sorts first, but "Foo, USA.zip" was packaged before.
+    let mut state = SystemState::default();
+    state.insert("Foo, USA.zip".to_string(), RomStateEntry::default());
+    let mut sources = [http("Foo (USA).zip", None), http("Foo, USA.zip", None)];
+
+    let collisions = resolve_romnames(&mut sources, &state, CollisionStrategy::Numeric);
+
+    assert_eq!(romnames(&sources), ["foousa-2", "foousa"]);
+    assert_eq!(collisions.len(), 1);
+    assert_eq!(collisions[0].filename, "Foo (USA).zip");
+    assert_eq!(collisions[0].base, "foousa");
+    assert_eq!(collisions[0].romname, "foousa-2");
+  }
+
+  #[test]
+  fn names_of_entries_out_of_the_run_stay_reserved() {
+    let mut state = SystemState::default();
+    state.insert(
+      "Foo (USA).zip".to_string(),
+      RomStateEntry {
+        romname: Some("foousa".to_string()),
+        ..Default::default()
+      },
+    );
+    let mut sources = [http("Foo, USA.zip", None)];
+
+    resolve_romnames(&mut sources, &state, CollisionStrategy::Numeric);
+
+    assert_eq!(romnames(&sources), ["foousa-2"]);
+  }
+
+  #[test]
+  fn sha1_strategy_suffixes_the_sha1_and_falls_back_to_numbers() {
+    let state = SystemState::default();
+    let mut sources = [
+      http("Foo (USA).zip", Some("0123456789")),
+      http("Foo, USA.zip", Some("ABCDEF0123")),
+      http("Foo USA.zip", None),
+    ];
+
+    resolve_romnames(&mut sources, &state, CollisionStrategy::Sha1);
+
+    // Filename order: "Foo (USA)", "Foo USA", "Foo, USA".
+    assert_eq!(
+      romnames(&sources),
+      ["foousa", "foousa-abcdef01", "foousa-2"]
+    );
+  }
+
+  #[test]
+  fn disambiguate_skips_taken_suffixes() {
+    let taken: HashSet<String> = ["foo", "foo-2", "foo-abcdef01"]
+      .into_iter()
+      .map(str::to_string)
+      .collect();
+    let numeric = disambiguate("foo", Some("abcdef01"), CollisionStrategy::Numeric, &taken);
+    let sha1_taken = disambiguate("foo", Some("ABCDEF0123"), CollisionStrategy::Sha1, &taken);
+    let short_sha1 = disambiguate("foo", Some("abc"), CollisionStrategy::Sha1, &taken);
+    assert_eq!(numeric, "foo-3");
+    assert_eq!(sha1_taken, "foo-3");
+    assert_eq!(short_sha1, "foo-3");
+  }
+}
@@ -0,0 +1,149 @@
+use std::{
+  collections::HashSet,
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use crate::{
+  conf::System,
+  package::{normalize_name, read_pkgname, read_system},
+  rom::RomSourceData,
+  state::SystemState,
+};
+
+/// How `--clean` disposes of orphaned output directories.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CleanMode {
+  /// Move directories to `<system>.archive/`.
+  Archive,
+  /// Delete directories.
+  Delete,
+}
+
+impl CleanMode {
+  pub fn parse(s: &str) -> Option<Self> {
+    match s {
+      "archive" => Some(Self::Archive),
+      "delete" => Some(Self::Delete),
+      _ => None,
+    }
+  }
+}
+
+/// A ROM that is no longer part of the collected sources.
+pub struct Orphan {
+  /// State key, `None` for an output directory without state entry.
+  pub filename: Option<String>,
+  /// Output directory, `None` if it no longer exists on disk.
+  pub dir: Option<PathBuf>,
+  /// Full package name to drop from the repository.
+  pub pkgname: String,
+}
+
+/// Lists state entries and output directories that no longer match any
+/// collected `RomSourceData` (ROM removed upstream, filter changed, …).
+///
+/// Output directories without state entry are only considered when they
+/// contain a PKGBUILD generated for this system (`_system`), so directories of
+/// other systems sharing the working directory are never reported, even when
+/// their `basename` starts with this one's.
+pub fn find_orphans(
+  sources: &[RomSourceData],
+  state: &SystemState,
+  system: &System,
+) -> Vec<Orphan> {
+  let current: HashSet<&str> = sources.iter().map(|s| s.filename.as_str()).collect();
+  let current_dirs: HashSet<PathBuf> = sources
+    .iter()
+    .map(|s| Path::new(&s.filename).with_extension(""))
+    .collect();
+
+  let mut orphans: Vec<Orphan> = state
+    .roms
+    .iter()
+    .filter(|(filename, _)| !current.contains(filename.as_str()))
+    .map(|(filename, entry)| {
+      let romname = entry
+        .romname
+        .clone()
+        .unwrap_or_else(|| normalize_name(filename));
+      // Two filenames differing only by extension share an output directory:
+      // never hand over a directory still used by a collected ROM.
+      let dir = Some(Path::new(filename).with_extension(""))
+        .filter(|d| d.is_dir() && !current_dirs.contains(d));
+      Orphan {
+        filename: Some(filename.clone()),
+        pkgname: dir
+          .as_deref()
+          .and_then(read_pkgname)
+          .unwrap_or_else(|| format!("{}{}", system.basename, romname)),
+        dir,
+      }
+    })
+    .collect();
+
+  let known_dirs: HashSet<PathBuf> = orphans.iter().filter_map(|o| o.dir.clone()).collect();
+  if let Ok(entries) = fs::read_dir(".") {
+    for entry in entries.flatten() {
+      let dir = PathBuf::from(entry.file_name());
+      if !entry.path().is_dir() || current_dirs.contains(&dir) || known_dirs.contains(&dir) {
+        continue;
+      }
+      if read_system(&dir).as_deref() != Some(system.name.as_str()) {
+        continue;
+      }
+      if let Some(pkgname) = read_pkgname(&dir) {
+        orphans.push(Orphan {
+          filename: None,
+          dir: Some(dir),
+          pkgname,
+        });
+      }
+    }
+  }
+
+  orphans.sort_by(|a, b| a.pkgname.cmp(&b.pkgname));
+  orphans
+}
+
+/// Writes the pkgnames of `orphans` to `path`, one per line, ready for
+/// `repo-remove`. Removes a stale list when there is no orphan.
+pub fn write_pkgname_list(path: &str, orphans: &[Orphan]) -> io::Result<()> {
+  if orphans.is_empty() {
+    if Path::new(path).exists() {
+      fs::remove_file(path)?;
+    }
+    return Ok(());
+  }
+  let list: String = orphans.iter().map(|o| format!("{}\n", o.pkgname)).collect();
+  fs::write(path, list)
+}
+
+/// Archives or deletes orphaned output directories and drops their state
+/// entries. The caller is responsible for saving the state afterwards.
+pub fn clean(
+  orphans: &[Orphan],
+  state: &mut SystemState,
+  mode: CleanMode,
+  archive_dir: &Path,
+) -> io::Result<()> {
+  for orphan in orphans {
+    if let Some(ref dir) = orphan.dir {
+      match mode {
+        CleanMode::Archive => {
+          fs::create_dir_all(archive_dir)?;
+          let dest = archive_dir.join(dir);
+          if dest.exists() {
+            fs::remove_dir_all(&dest)?;
+          }
+          fs::rename(dir, dest)?;
+        }
+        CleanMode::Delete => fs::remove_dir_all(dir)?,
+      }
+    }
+    if let Some(ref filename) = orphan.filename {
+      state.roms.remove(filename);
+    }
+  }
+  Ok(())
+}
@@ -1,9 +1,10 @@
+use glob::Pattern;
 use minijinja::{context, Environment};
 use serde::Serialize;
 use snafu::{ResultExt, Snafu};
-use std::{fs::create_dir_all, path::Path};
+use std::{collections::BTreeMap, fs::create_dir_all, os::unix::fs::PermissionsExt, path::Path};
 
-use super::conf::System;
+use super::conf::{Launcher, MediaSource, System};
 use super::emulationstation::Game;
 use screenscraper::jeuinfo::{JeuInfo, Media};
 
@@ -26,11 +27,23 @@ pub struct Package {
   pub disc1_filename: String,
   pub rom_url: String,
   pub hash: String,
+  /// ROM md5 / crc32 for the optional `md5` and `hash` description fields.
+  pub md5: Option<String>,
+  pub crc32: Option<String>,
   pub jeu: Option<JeuInfo>,
-  pub name: String,
+  /// Collision-free normalised name (`_romname`, pkgname suffix, media dir).
+  /// Defaults to `normalize_name(rom)`; overridden with the name resolved at
+  /// collection time.
+  pub romname: String,
+  /// Previous romnames of this ROM (upstream renames), emitted as
+  /// `replaces=()` / `conflicts=()` so pacman upgrades cleanly.
+  pub replaces: Vec<String>,
   pub medias: Medias,
   /// (filename, rom_url, sha1) for disc 2, 3, …  Empty for single-disc.
+  /// For a game folder, the files installed along with the main one.
   pub extra_discs: Vec<(String, String, String)>,
+  /// Folder the game files are installed in (folder source game folders).
+  pub game_dir: Option<String>,
 }
 
 #[derive(Debug, Snafu)]
@@ -48,7 +61,7 @@ fn media_region(url: &str) -> &str {
   url.find("media=").map(|i| &url[i + 6..]).unwrap_or("")
 }
 
-fn render_template(src: &str, ctx: &minijinja::Value) -> String {
+pub fn render_template(src: &str, ctx: &minijinja::Value) -> String {
   let mut env = Environment::new();
   env.set_trim_blocks(true);
   env.set_lstrip_blocks(true);
@@ -56,7 +69,7 @@ fn render_template(src: &str, ctx: &minijinja::Value) -> String {
   env.get_template("t").unwrap().render(ctx).unwrap()
 }
 
-fn generate_description_xml(game: &Game) -> String {
+pub fn generate_description_xml(game: &Game) -> String {
   let mut xml = String::new();
   let mut ser = quick_xml::se::Serializer::new(&mut xml);
   ser.indent(' ', 2);
@@ -64,13 +77,221 @@ fn generate_description_xml(game: &Game) -> String {
   xml
 }
 
+/// Returns the output filename for a downloaded media asset.
+pub fn media_filename(kind: &str, format: &str) -> String {
+  match kind {
+    "video" => "video.mp4".to_string(),
+    "manual" => "manual.pdf".to_string(),
+    _ => format!("{}.{}", kind, format),
+  }
+}
+
+/// Reconstructs the public ScreenScraper media URL for `kind`.
+fn ss_media_url(system_id: u32, jeu_id: &str, kind: &str, x: &Media) -> String {
+  let base = format!("https://screenscraper.fr/medias/{}/{}", system_id, jeu_id);
+  match kind {
+    "video" => format!("{}/video.mp4", base),
+    "bezel" => format!(
+      "{}/bezel-16-9({}).{}",
+      base,
+      x.region.as_deref().unwrap_or("wor"),
+      x.format
+    ),
+    "marquee" => format!("{}/marquee.{}", base, x.format),
+    "screenshot" => format!(
+      "{}/ss({}).{}",
+      base,
+      x.region.as_deref().unwrap_or("wor"),
+      x.format
+    ),
+    "manual" => format!("{}/{}.pdf", base, media_region(&x.url)),
+    _ => format!("{}/{}.{}", base, media_region(&x.url), x.format),
+  }
+}
+
+/// Returns the media URL returned by ScreenScraper without the account
+/// parameters it embeds (`devid`, `devpassword`, `ssid`, `sspassword`,
+/// `softname`), so credentials never end up in a PKGBUILD.
+fn public_media_url(url: &str) -> String {
+  let Some((base, query)) = url.split_once('?') else {
+    return url.to_string();
+  };
+  let params: Vec<&str> = query
+    .split('&')
+    .filter(|p| {
+      let key = p.split('=').next().unwrap_or("");
+      !matches!(
+        key,
+        "devid" | "devpassword" | "ssid" | "sspassword" | "softname"
+      )
+    })
+    .collect();
+  if params.is_empty() {
+    base.to_string()
+  } else {
+    format!("{}?{}", base, params.join("&"))
+  }
+}
+
+/// Builds the PKGBUILD `source` entry for a ROM file.
+///
+/// An empty `url` (folder source without mirror) yields a plain local source:
+/// makepkg then takes the file from the package directory, where `CopyRom`
+/// put it.
+fn rom_source(filename: &str, url: &str) -> String {
+  let escaped = filename.replace("'", "'\\''");
+  if url.is_empty() {
+    escaped
+  } else {
+    format!("{}::{}", escaped, url)
+  }
+}
+
+/// Name of a ROM file in its package directory and in the PKGBUILD sources.
+/// makepkg keeps only the last component of a source path, so the `/` of a
+/// file nested in a game folder are percent-encoded.
+pub fn package_file(name: &str) -> String {
+  name.replace('/', "%2F")
+}
+
+/// Percent-encodes a filename for use as a URL path segment.
+pub fn encode_path_segment(segment: &str) -> String {
+  segment
+    .bytes()
+    .map(|b| match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        (b as char).to_string()
+      }
+      _ => format!("%{:02X}", b),
+    })
+    .collect()
+}
+
+/// Per-game `batocera.conf` snippet shipped in the package directory.
+const GAME_CONF: &str = "batocera.conf";
+
+/// Every built-in template a ROM package is rendered from, part of the
+/// system fingerprint (`fingerprint::compute`).
+pub const PKGBUILD_TEMPLATES: &[&str] = &[
+  include_str!("../assets/templates/pkgbuild/pkgbuild.jinja"),
+  include_str!("../assets/templates/pkgbuild/install.jinja"),
+  include_str!("../assets/templates/pkgbuild/default-build.jinja"),
+  include_str!("../assets/templates/pkgbuild/default-package.jinja"),
+  include_str!("../assets/templates/pkgbuild/gamedir-package.jinja"),
+  include_str!("../assets/templates/pkgbuild/segacd-build.jinja"),
+  include_str!("../assets/templates/pkgbuild/segacd-package.jinja"),
+  include_str!("../assets/templates/pkgbuild/psx-build.jinja"),
+  include_str!("../assets/templates/pkgbuild/psx-package.jinja"),
+  include_str!("../assets/templates/pkgbuild/ps2-build.jinja"),
+  include_str!("../assets/templates/pkgbuild/ps2-package.jinja"),
+  include_str!("../assets/templates/pkgbuild/multidisc-build.jinja"),
+  include_str!("../assets/templates/pkgbuild/multidisc-package.jinja"),
+];
+
+/// Renders the `batocera.conf` lines (`<dir>["<game file>"].<key>=<value>`) of
+/// the `game_config` rules matching this ROM by filename or ScreenScraper
+/// game id. Returns `None` when no rule applies.
+fn game_conf_snippet(system: &System, game: &Game, rom: &str, jeu_id: &str) -> Option<String> {
+  let mut settings: BTreeMap<&str, &str> = BTreeMap::new();
+  for rule in &system.game_config {
+    // Patterns are checked by `Conf::load`.
+    let by_name = rule
+      .pattern
+      .as_deref()
+      .and_then(|p| Pattern::new(p).ok())
+      .is_some_and(|p| p.matches(rom));
+    let by_id = rule
+      .game_id
+      .is_some_and(|id| jeu_id.parse::<u32>() == Ok(id));
+    if by_name || by_id {
+      settings.extend(rule.settings.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+    }
+  }
+  if settings.is_empty() {
+    return None;
+  }
+  let file = game.path.strip_prefix("./").unwrap_or(&game.path);
+  Some(
+    settings
+      .iter()
+      .map(|(key, value)| format!("{}[\"{}\"].{}={}\n", system.dir, file, key, value))
+      .collect(),
+  )
+}
+
+/// Template context shared by a launcher's content, filename and game path.
+fn launcher_context(
+  system: &System,
+  launcher: &Launcher,
+  game: &Game,
+  rom: &str,
+  romname: &str,
+) -> minijinja::Value {
+  let stem = Path::new(rom)
+    .file_stem()
+    .and_then(|s| s.to_str())
+    .unwrap_or(rom);
+  context! {
+    rom => rom.replace("'", "'\\''"),
+    name => game.name,
+    romname => romname,
+    stem => stem,
+    dir => system.dir,
+    args => launcher.args.get(rom).cloned().unwrap_or_default(),
+  }
+}
+
+/// Renders the launcher filename of `game`, `None` when the system has no
+/// launcher.
+///
+/// The name may come from ScreenScraper (`{{ name }}`) and ends up in the
+/// PKGBUILD, quoted: path separators, quotes, `$`, backquotes, backslashes and
+/// control characters are replaced with `_`.
+fn launcher_filename(system: &System, game: &Game, rom: &str, romname: &str) -> Option<String> {
+  let launcher = system.launcher.as_ref()?;
+  let ctx = launcher_context(system, launcher, game, rom, romname);
+  let filename: String = render_template(&launcher.filename, &ctx)
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' | '"' | '\'' | '$' | '`' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .collect();
+  Some(match filename.trim() {
+    "" | "." | ".." => format!("{}.launcher", romname),
+    _ => filename,
+  })
+}
+
 /// Sets `game.path` to the system-specific value without performing any I/O.
 ///
-/// `is_multi_disc` is true when the ROM is part of a multi-disc group; any
-/// system (except OpenBOR) will then use a `.m3u` playlist as the game path.
-fn apply_game_path(system: &System, game: &mut Game, romname: &str, is_multi_disc: bool) {
+/// Systems with a launcher point at it (`launcher.path`, `./<launcher>` by
+/// default). Game folders point at their main file inside the folder.
+/// Otherwise `is_multi_disc` is true when the ROM is part of a multi-disc
+/// group; the game path is then a `.m3u` playlist.
+fn apply_game_path(
+  system: &System,
+  game: &mut Game,
+  rom: &str,
+  romname: &str,
+  game_dir: Option<&str>,
+  is_multi_disc: bool,
+) {
+  if let Some(ref launcher) = system.launcher {
+    let filename = launcher_filename(system, game, rom, romname).unwrap_or_default();
+    let ctx = context! {
+      launcher => filename,
+      ..launcher_context(system, launcher, game, rom, romname)
+    };
+    game.path = render_template(launcher.path.as_deref().unwrap_or("./{{ launcher }}"), &ctx);
+    return;
+  }
+  if let Some(dir) = game_dir {
+    game.path = format!("./{}/{}", dir, rom);
+    return;
+  }
   match system.id {
-    214 => game.path = format!("./{}.sh", game.name),
     22 | 57 => game.path = format!("./{}.m3u", romname),
     _ if is_multi_disc => game.path = format!("./{}.m3u", romname),
     _ => {}
@@ -93,31 +314,100 @@ pub fn read_pkgver(directory: &Path) -> u32 {
     .unwrap_or(0)
 }
 
-impl Package {
-  pub fn normalize_name(&self) -> String {
-    let stem = Path::new(&self.name)
-      .file_stem()
-      .and_then(|s| s.to_str())
-      .unwrap_or(&self.name);
-    stem
-      .replace("(", "")
-      .replace(")", "")
-      .replace(" ", "")
-      .replace(",", "")
-      .replace("'", "")
-      .replace("!", "")
-      .replace("&", "and")
-      .replace("%", "")
-      .replace("^", "")
-      .replace(";", "")
-      .replace("$", "")
-      .replace("~", "-")
-      .replace("=", "-")
-      .replace("[", "")
-      .replace("]", "")
-      .to_lowercase()
-  }
+/// Normalises a ROM filename into the `_romname` / pkgname suffix.
+///
+/// Punctuation and spaces are stripped, so distinct ROMs may map to the same
+/// name — see `naming::resolve_romnames` for collision handling.
+pub fn normalize_name(name: &str) -> String {
+  let stem = Path::new(name)
+    .file_stem()
+    .and_then(|s| s.to_str())
+    .unwrap_or(name);
+  stem
+    .replace("(", "")
+    .replace(")", "")
+    .replace(" ", "")
+    .replace(",", "")
+    .replace("'", "")
+    .replace("!", "")
+    .replace("&", "and")
+    .replace("%", "")
+    .replace("^", "")
+    .replace(";", "")
+    .replace("$", "")
+    .replace("~", "-")
+    .replace("=", "-")
+    .replace("[", "")
+    .replace("]", "")
+    .to_lowercase()
+}
+
+/// Reads the value of the variable `name` from an existing PKGBUILD, quotes
+/// and array parentheses removed.
+fn read_variable(directory: &Path, name: &str) -> Option<String> {
+  let path = directory.join("PKGBUILD");
+  std::fs::read_to_string(path)
+    .ok()?
+    .lines()
+    .find_map(|line| {
+      line
+        .strip_prefix(name)
+        .and_then(|v| v.strip_prefix('='))
+        .map(|v| v.trim_matches(|c| matches!(c, '(' | ')' | '\'' | '"')))
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+    })
+}
+
+/// Reads the first `pkgname` from an existing PKGBUILD (`pkgname=('x')`).
+/// Returns `None` if the file doesn't exist or has no parsable pkgname.
+pub fn read_pkgname(directory: &Path) -> Option<String> {
+  read_variable(directory, "pkgname")
+}
+
+/// Reads the name of the system an existing PKGBUILD was generated for
+/// (`_system="x"`). Returns `None` if the file doesn't exist or predates it.
+pub fn read_system(directory: &Path) -> Option<String> {
+  read_variable(directory, "_system")
+}
+
+/// Reads the `source=()` entries of an existing PKGBUILD, as rendered (outer
+/// quotes removed). Returns `None` if there is no PKGBUILD yet.
+pub fn read_sources(directory: &Path) -> Option<Vec<String>> {
+  let pkgbuild = std::fs::read_to_string(directory.join("PKGBUILD")).ok()?;
+  Some(
+    pkgbuild
+      .lines()
+      .skip_while(|line| *line != "source=(")
+      .skip(1)
+      .take_while(|line| *line != ")")
+      .map(|line| {
+        let line = line.trim();
+        line
+          .strip_prefix('\'')
+          .and_then(|l| l.strip_suffix('\''))
+          .unwrap_or(line)
+          .to_string()
+      })
+      .collect(),
+  )
+}
+
+/// Name of the file a `source=()` entry (as returned by `read_sources`) is
+/// found under in the package directory: the part before `::`, unescaped.
+pub fn source_file(source: &str) -> String {
+  let name = source.split_once("::").map_or(source, |(name, _)| name);
+  name.replace("'\\''", "'")
+}
+
+/// Returns `Some(true)` if an existing PKGBUILD declares an `install=`
+/// script, `None` if there is no PKGBUILD yet.
+pub fn has_install_script(directory: &Path) -> Option<bool> {
+  let pkgbuild = std::fs::read_to_string(directory.join("PKGBUILD")).ok()?;
+  Some(pkgbuild.lines().any(|line| line.starts_with("install=")))
+}
 
+impl Package {
   pub fn new(
     mut jeu: Option<JeuInfo>,
     file: &str,
@@ -144,16 +434,20 @@ impl Package {
       disc1_filename: disc1_filename.to_string(),
       rom_url: url.to_string(),
       hash: hash.to_string(),
+      md5: None,
+      crc32: None,
       jeu,
-      name: file.to_string(),
+      romname: normalize_name(file),
+      replaces: Vec::new(),
       medias,
       extra_discs,
+      game_dir: None,
     })
   }
 
   /// Returns `true` when this ROM is part of a multi-disc group.
   pub fn is_multi_disc(&self) -> bool {
-    !self.extra_discs.is_empty()
+    !self.extra_discs.is_empty() && self.game_dir.is_none()
   }
 
   /// Writes description.xml only when the content has changed.
@@ -169,44 +463,138 @@ impl Package {
     Ok(true)
   }
 
-  fn write_launcher(&self, system: &System, game: &mut Game, romname: &str) -> Result<()> {
-    if system.id == 214 {
-      let ctx = context! {
-        rom => self.rom.replace("'", "'\\''"),
-      };
-      let launcher = render_template(
-        include_str!("../assets/templates/launcher/openbor.jinja"),
-        &ctx,
-      );
-      std::fs::write("./launcher", launcher).context(WriteResultSnafu {
-        filename: "./launcher".to_string(),
-      })?;
+  /// Writes the launcher of port-style systems into the package directory.
+  fn write_launcher(&self, system: &System, game: &Game, directory: &Path) -> Result<()> {
+    let Some(ref launcher) = system.launcher else {
+      return Ok(());
+    };
+    let ctx = launcher_context(system, launcher, game, &self.rom, &self.romname);
+    let content = render_template(&launcher.source, &ctx);
+    let filename = launcher_filename(system, game, &self.rom, &self.romname).unwrap_or_default();
+    let path = format!("{}/{}", directory.display(), filename);
+    std::fs::write(&path, content).context(WriteResultSnafu {
+      filename: path.clone(),
+    })?;
+    // Executable, so a copy outside pacman (`--export`) can be run as-is.
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+      .context(WriteResultSnafu { filename: path })
+  }
+
+  /// Writes the per-game `batocera.conf` snippet, or removes a leftover one.
+  fn write_game_conf(&self, snippet: Option<&str>, directory: &Path) -> Result<()> {
+    let path = format!("{}/{}", directory.display(), GAME_CONF);
+    match snippet {
+      Some(snippet) => std::fs::write(&path, snippet).context(WriteResultSnafu { filename: path }),
+      None => {
+        std::fs::remove_file(&path).ok();
+        Ok(())
+      }
+    }
+  }
+
+  /// Writes `<pkgname>.install` when the system uses pacman install hooks or
+  /// the package ships a `batocera.conf` snippet, and removes a leftover one
+  /// otherwise. Returns the `install=` value (empty without script).
+  fn write_install(
+    &self,
+    system: &System,
+    pkgname: &str,
+    directory: &Path,
+    game_conf: bool,
+  ) -> Result<String> {
+    // Only one .install file per directory: drop those of a previous pkgname.
+    if let Ok(entries) = std::fs::read_dir(directory) {
+      for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("install") {
+          std::fs::remove_file(path).ok();
+        }
+      }
     }
-    apply_game_path(system, game, romname, self.is_multi_disc());
-    Ok(())
+    if !system.install_hooks && !game_conf {
+      return Ok(String::new());
+    }
+    let ctx = context! {
+      dir => system.dir,
+      romname => self.romname,
+      pkgname => pkgname,
+      gamelist => system.install_hooks,
+      game_conf => game_conf,
+    };
+    let script = render_template(
+      include_str!("../assets/templates/pkgbuild/install.jinja"),
+      &ctx,
+    );
+    let filename = format!("{}.install", pkgname);
+    let path = format!("{}/{}", directory.display(), filename);
+    std::fs::write(&path, script).context(WriteResultSnafu { filename: path })?;
+    Ok(filename)
+  }
+
+  /// `source=()` entries of the ROM files: disc 1, then the extra discs.
+  fn rom_sources(&self) -> Vec<String> {
+    std::iter::once(rom_source(&self.disc1_filename, &self.rom_url))
+      .chain(
+        self
+          .extra_discs
+          .iter()
+          .map(|(filename, url, _)| rom_source(&package_file(filename), url)),
+      )
+      .collect()
+  }
+
+  /// `source=()` entries of the media, with their sha1, in PKGBUILD order.
+  /// URLs follow the system's `media_source`.
+  fn media_sources(&self, system: &System) -> Vec<(String, String)> {
+    let directory = Path::new(&self.rom).with_extension("");
+    let jeu_id = self.jeu.as_ref().map(|j| j.id.as_str()).unwrap_or("");
+    [
+      ("video", self.medias.video.as_ref()),
+      ("bezel", self.medias.bezel.as_ref()),
+      ("image", self.medias.image.as_ref()),
+      ("thumbnail", self.medias.thumbnail.as_ref()),
+      ("marquee", self.medias.marquee.as_ref()),
+      ("screenshot", self.medias.screenshot.as_ref()),
+      ("wheel", self.medias.wheel.as_ref()),
+      ("manual", self.medias.manual.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(kind, media)| {
+      let x = media?;
+      let local = media_filename(kind, &x.format);
+      let url = match system.media_source {
+        MediaSource::ScreenScraper => ss_media_url(system.id, jeu_id, kind, x),
+        MediaSource::SsUrl => public_media_url(&x.url),
+        MediaSource::SsUrlWithCredentials => x.url.clone(),
+        MediaSource::Mirror(ref base) => format!(
+          "{}/{}/{}",
+          base.trim_end_matches('/'),
+          encode_path_segment(&directory.to_string_lossy()),
+          local
+        ),
+      };
+      Some((format!("{}::{}", local, url), x.sha1.clone()))
+    })
+    .collect()
   }
 
   pub fn build_pkgbuild(&mut self, system: &System, game: &Game, pkgver: u32) -> Result<()> {
-    let romname = self.normalize_name();
+    let romname = self.romname.clone();
     let rom_escaped = self.rom.replace("$", "\\$");
     let directory = Path::new(&self.rom).with_extension("");
     let jeu_id = self.jeu.as_ref().map(|j| j.id.as_str()).unwrap_or("");
 
+    let game_conf = game_conf_snippet(system, game, &self.rom, jeu_id);
+    self.write_game_conf(game_conf.as_deref(), &directory)?;
+
     // Sources & checksums
     let mut sources: Vec<String> = Vec::new();
     let mut sha1sums: Vec<String> = Vec::new();
 
-    // Disc 1 (or the only disc for single-disc games).
-    let disc1_escaped = self.disc1_filename.replace("'", "'\\''");
-    sources.push(format!("{}::{}", disc1_escaped, self.rom_url));
+    // Disc 1 (or the only disc for single-disc games), then extra discs.
+    sources.extend(self.rom_sources());
     sha1sums.push(self.hash.clone());
-
-    // Extra discs (disc 2, 3, …).
-    for (disc_filename, disc_url, disc_sha1) in &self.extra_discs {
-      let escaped = disc_filename.replace("'", "'\\''");
-      sources.push(format!("{}::{}", escaped, disc_url));
-      sha1sums.push(disc_sha1.clone());
-    }
+    sha1sums.extend(self.extra_discs.iter().map(|(_, _, sha1)| sha1.clone()));
 
     sources.push("description.xml".to_string());
     sha1sums.push(checksums::hash_file(
@@ -214,83 +602,26 @@ impl Package {
       checksums::Algorithm::SHA1,
     ));
 
-    if let Some(ref x) = self.medias.video {
-      sources.push(format!(
-        "video.mp4::https://screenscraper.fr/medias/{}/{}/video.mp4",
-        system.id, jeu_id
+    if game_conf.is_some() {
+      sources.push(GAME_CONF.to_string());
+      sha1sums.push(checksums::hash_file(
+        &directory.join(GAME_CONF),
+        checksums::Algorithm::SHA1,
       ));
-      sha1sums.push(x.sha1.clone());
     }
-    if let Some(ref x) = self.medias.bezel {
-      sources.push(format!(
-        "bezel.{}::https://screenscraper.fr/medias/{}/{}/bezel-16-9({}).{}",
-        x.format,
-        system.id,
-        jeu_id,
-        x.region.as_deref().unwrap_or("wor"),
-        x.format
-      ));
-      sha1sums.push(x.sha1.clone());
-    }
-    if let Some(ref x) = self.medias.image {
-      sources.push(format!(
-        "image.{}::https://screenscraper.fr/medias/{}/{}/{}.{}",
-        x.format,
-        system.id,
-        jeu_id,
-        media_region(&x.url),
-        x.format
-      ));
-      sha1sums.push(x.sha1.clone());
-    }
-    if let Some(ref x) = self.medias.thumbnail {
-      sources.push(format!(
-        "thumbnail.{}::https://screenscraper.fr/medias/{}/{}/{}.{}",
-        x.format,
-        system.id,
-        jeu_id,
-        media_region(&x.url),
-        x.format
-      ));
-      sha1sums.push(x.sha1.clone());
-    }
-    if let Some(ref x) = self.medias.marquee {
-      sources.push(format!(
-        "marquee.{}::https://screenscraper.fr/medias/{}/{}/marquee.{}",
-        x.format, system.id, jeu_id, x.format
-      ));
-      sha1sums.push(x.sha1.clone());
-    }
-    if let Some(ref x) = self.medias.screenshot {
-      sources.push(format!(
-        "screenshot.{}::https://screenscraper.fr/medias/{}/{}/ss({}).{}",
-        x.format,
-        system.id,
-        jeu_id,
-        x.region.as_deref().unwrap_or("wor"),
-        x.format
-      ));
-      sha1sums.push(x.sha1.clone());
-    }
-    if let Some(ref x) = self.medias.wheel {
-      sources.push(format!(
-        "wheel.{}::https://screenscraper.fr/medias/{}/{}/{}.{}",
-        x.format,
-        system.id,
-        jeu_id,
-        media_region(&x.url),
-        x.format
+
+    let launcher = launcher_filename(system, game, &self.rom, &romname);
+    if let Some(ref filename) = launcher {
+      sources.push(filename.clone());
+      sha1sums.push(checksums::hash_file(
+        &directory.join(filename),
+        checksums::Algorithm::SHA1,
       ));
-      sha1sums.push(x.sha1.clone());
     }
-    if let Some(ref x) = self.medias.manual {
-      sources.push(format!(
-        "manual.pdf::https://screenscraper.fr/medias/{}/{}/{}.pdf",
-        system.id,
-        jeu_id,
-        media_region(&x.url)
-      ));
-      sha1sums.push(x.sha1.clone());
+
+    for (source, sha1) in self.media_sources(system) {
+      sources.push(source);
+      sha1sums.push(sha1);
     }
 
     // Extension of the disc files (used by multi-disc templates).
@@ -301,28 +632,48 @@ impl Package {
       .to_string();
 
     // System-specific build/package sections
-    let sys_ctx = context! { dir => system.dir, rom => rom_escaped, ext => disc_ext };
-    let (build_src, package_src) = match system.id {
-      20 => (
-        include_str!("../assets/templates/pkgbuild/segacd-build.jinja"),
-        include_str!("../assets/templates/pkgbuild/segacd-package.jinja"),
-      ),
-      22 => (
-        include_str!("../assets/templates/pkgbuild/psx-build.jinja"),
-        include_str!("../assets/templates/pkgbuild/psx-package.jinja"),
-      ),
-      57 => (
-        include_str!("../assets/templates/pkgbuild/ps2-build.jinja"),
-        include_str!("../assets/templates/pkgbuild/ps2-package.jinja"),
-      ),
-      _ if self.is_multi_disc() => (
-        include_str!("../assets/templates/pkgbuild/multidisc-build.jinja"),
-        include_str!("../assets/templates/pkgbuild/multidisc-package.jinja"),
-      ),
-      _ => (
+    let sys_ctx = context! {
+      dir => system.dir,
+      rom => rom_escaped,
+      ext => disc_ext,
+      batoexec => !system.install_hooks,
+      launcher => launcher.unwrap_or_default(),
+      game_dir => self.game_dir.as_deref().map(|d| d.replace("$", "\\$")),
+      files => std::iter::once(context! { source => rom_escaped, path => rom_escaped })
+        .chain(self.extra_discs.iter().map(|(f, _, _)| context! {
+          source => package_file(f).replace("$", "\\$"),
+          path => f.replace("$", "\\$"),
+        }))
+        .collect::<Vec<_>>(),
+    };
+    let (build_src, package_src) = if self.game_dir.is_some() {
+      (
         include_str!("../assets/templates/pkgbuild/default-build.jinja"),
-        include_str!("../assets/templates/pkgbuild/default-package.jinja"),
-      ),
+        include_str!("../assets/templates/pkgbuild/gamedir-package.jinja"),
+      )
+    } else {
+      match system.id {
+        20 => (
+          include_str!("../assets/templates/pkgbuild/segacd-build.jinja"),
+          include_str!("../assets/templates/pkgbuild/segacd-package.jinja"),
+        ),
+        22 => (
+          include_str!("../assets/templates/pkgbuild/psx-build.jinja"),
+          include_str!("../assets/templates/pkgbuild/psx-package.jinja"),
+        ),
+        57 => (
+          include_str!("../assets/templates/pkgbuild/ps2-build.jinja"),
+          include_str!("../assets/templates/pkgbuild/ps2-package.jinja"),
+        ),
+        _ if self.is_multi_disc() => (
+          include_str!("../assets/templates/pkgbuild/multidisc-build.jinja"),
+          include_str!("../assets/templates/pkgbuild/multidisc-package.jinja"),
+        ),
+        _ => (
+          include_str!("../assets/templates/pkgbuild/default-build.jinja"),
+          include_str!("../assets/templates/pkgbuild/default-package.jinja"),
+        ),
+      }
     };
     let build_section = render_template(build_src, &sys_ctx);
     let package_section = render_template(package_src, &sys_ctx);
@@ -333,14 +684,25 @@ impl Package {
     } else {
       format!("https://screenscraper.fr/gameinfos.php?gameid={}", jeu_id)
     };
+    let replaces: Vec<String> = self
+      .replaces
+      .iter()
+      .map(|r| format!("{}{}", system.basename, r))
+      .collect();
+    let pkgname = format!("{}{}", system.basename, romname);
+    let install = self.write_install(system, &pkgname, &directory, game_conf.is_some())?;
     let ctx = context! {
-      pkgname => format!("{}{}", system.basename, romname),
+      pkgname => pkgname,
+      system => system.name,
       romname => romname,
       pkgver => pkgver,
       pkgrel => 1_u32,
       pkgdesc => &game.name,
       url => url,
       depends => system.depends.as_deref().unwrap_or(""),
+      replaces => replaces,
+      install => install,
+      game_conf => if game_conf.is_some() { GAME_CONF } else { "" },
       sources => sources,
       sha1sums => sha1sums,
       build_section => build_section,
@@ -357,8 +719,15 @@ impl Package {
   /// Builds the complete `Game` struct with all media paths and system-specific
   /// path applied. Used by both `build()` and `check_description_changed()`.
   fn make_game(&self, system: &System, lang: &[&str]) -> (Game, String) {
-    let romname = self.normalize_name();
+    let romname = self.romname.clone();
     let mut game = Game::from_jeuinfo(&self.jeu, &self.rom, lang);
+    game.apply_extra_fields(
+      &system.extra_fields,
+      &self.jeu,
+      self.md5.as_deref(),
+      self.crc32.as_deref(),
+      lang,
+    );
 
     if let Some(x) = &self.medias.thumbnail {
       game.image = Some(format!("./data/{}/thumbnail.{}", romname, x.format));
@@ -382,7 +751,14 @@ impl Package {
       game.manual = Some(format!("./data/{}/manual.pdf", romname));
     }
 
-    apply_game_path(system, &mut game, &romname, self.is_multi_disc());
+    apply_game_path(
+      system,
+      &mut game,
+      &self.rom,
+      &romname,
+      self.game_dir.as_deref(),
+      self.is_multi_disc(),
+    );
     (game, romname)
   }
 
@@ -397,16 +773,52 @@ impl Package {
     existing != xml
   }
 
+  /// True when the per-game `batocera.conf` snippet would differ from (or
+  /// appear in, or vanish from) the package directory.
+  pub fn check_game_conf_changed(&self, system: &System, lang: &[&str]) -> bool {
+    let (game, _) = self.make_game(system, lang);
+    let directory = Path::new(&self.rom).with_extension("");
+    let jeu_id = self.jeu.as_ref().map(|j| j.id.as_str()).unwrap_or("");
+    let snippet = game_conf_snippet(system, &game, &self.rom, jeu_id);
+    let existing = std::fs::read_to_string(directory.join(GAME_CONF)).ok();
+    existing != snippet
+  }
+
+  /// True when the ROM or media entries of the existing PKGBUILD's
+  /// `source=()` differ from the current ones (mirror added, changed or
+  /// removed, `media_source` switched), or when there is no PKGBUILD yet.
+  pub fn check_sources_changed(&self, system: &System) -> bool {
+    let directory = Path::new(&self.rom).with_extension("");
+    let Some(existing) = read_sources(&directory) else {
+      return true;
+    };
+    let roms = self.rom_sources();
+    if !existing.starts_with(&roms) {
+      return true;
+    }
+    // Media are the downloaded (`name::url`) entries after the ROM files.
+    let medias: Vec<String> = self
+      .media_sources(system)
+      .into_iter()
+      .map(|(source, _)| source)
+      .collect();
+    let existing_medias: Vec<&String> = existing[roms.len()..]
+      .iter()
+      .filter(|source| source.contains("::"))
+      .collect();
+    existing_medias != medias.iter().collect::<Vec<_>>()
+  }
+
   /// Builds PKGBUILD + description.xml. Returns `true` if description.xml was
   /// written (new or updated content), `false` if it was already up-to-date.
   pub fn build(&mut self, system: &System, lang: &[&str], pkgver: u32) -> Result<bool> {
-    let (mut game, romname) = self.make_game(system, lang);
-
-    self.write_launcher(system, &mut game, &romname)?;
+    let (game, _) = self.make_game(system, lang);
 
     let directory = Path::new(&self.rom).with_extension("");
     create_dir_all(&directory).ok();
 
+    self.write_launcher(system, &game, &directory)?;
+
     let description_changed = self.write_description_xml(&game, &directory)?;
     self.build_pkgbuild(system, &game, pkgver)?;
     Ok(description_changed)
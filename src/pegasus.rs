@@ -0,0 +1,107 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+  conf::{PegasusExport, System},
+  frontend::{link_or_copy, FrontendEntry},
+};
+
+/// rompom media kind → Pegasus asset key.
+const ASSETS: &[(&str, &str)] = &[
+  ("thumbnail", "boxFront"),
+  ("image", "titlescreen"),
+  ("screenshot", "screenshot"),
+  ("wheel", "logo"),
+  ("marquee", "marquee"),
+  ("video", "video"),
+];
+
+/// Formats a multi-line Pegasus value: continuation lines are indented and
+/// empty lines are written as a single `.`.
+fn multiline(text: &str) -> String {
+  text
+    .trim()
+    .lines()
+    .map(|line| match line.trim() {
+      "" => "  .".to_string(),
+      line => format!("  {}", line),
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// `releasedate` (`%Y%m%dT%H%M%S`) → `YYYY-MM-DD`. Dates rompom defaults to
+/// when ScreenScraper has none (1970-01-01) are dropped.
+fn release(releasedate: &str) -> Option<String> {
+  let date = releasedate
+    .get(..8)
+    .filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+  if date == "19700101" {
+    return None;
+  }
+  Some(format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..]))
+}
+
+/// Writes `<dir>/metadata.pegasus.txt` under `root`, with media copied to
+/// `<dir>/media/<rom stem>/<asset>.<ext>` next to it.
+///
+/// Games are added to a collection named after the system; copy the `<dir>`
+/// folder over the ROM directory on the device. Returns the number of games
+/// written.
+pub fn export(
+  root: &Path,
+  system: &System,
+  conf: &PegasusExport,
+  entries: &[FrontendEntry],
+) -> io::Result<usize> {
+  let dir = root.join(&system.dir);
+  fs::create_dir_all(&dir)?;
+
+  let mut out = format!("collection: {}\nshortname: {}\n", system.name, system.dir);
+  if let Some(ref launch) = conf.launch {
+    out.push_str(&format!("launch: {}\n", launch));
+  }
+
+  for entry in entries {
+    let game = &entry.game;
+    out.push_str(&format!(
+      "\ngame: {}\nfile: {}\n",
+      game.name,
+      entry.rom_path()
+    ));
+    for (key, value) in [
+      ("developer", &game.developer),
+      ("publisher", &game.publisher),
+      ("genre", &game.genre),
+    ] {
+      if !value.is_empty() {
+        out.push_str(&format!("{}: {}\n", key, value));
+      }
+    }
+    if !game.players.is_empty() && game.players != "Unknown" {
+      out.push_str(&format!("players: {}\n", game.players));
+    }
+    if let Some(date) = release(&game.releasedate) {
+      out.push_str(&format!("release: {}\n", date));
+    }
+    if game.rating > 0.0 {
+      out.push_str(&format!("rating: {:.0}%\n", game.rating * 100.0));
+    }
+    if !game.desc.trim().is_empty() {
+      out.push_str(&format!("description:\n{}\n", multiline(&game.desc)));
+    }
+
+    let media_dir = Path::new("media").join(entry.rom_stem());
+    for &(kind, asset) in ASSETS {
+      let Some(src) = entry.media(kind) else {
+        continue;
+      };
+      let ext = src.extension().and_then(|e| e.to_str()).unwrap_or_default();
+      let rel = media_dir.join(format!("{}.{}", asset, ext));
+      link_or_copy(&src, &dir.join(&rel))?;
+      out.push_str(&format!("assets.{}: {}\n", asset, rel.display()));
+    }
+  }
+
+  fs::write(dir.join("metadata.pegasus.txt"), out)?;
+  Ok(entries.len())
+}
@@ -0,0 +1,328 @@
+use std::{
+  collections::HashSet,
+  fs,
+  path::{Path, PathBuf},
+  time::UNIX_EPOCH,
+};
+
+use checksums::{hash_file, Algorithm};
+
+use crate::{
+  package::{normalize_name, package_file},
+  rom::{HttpSource, IaSource, RomSource, RomSourceData},
+  state::SystemState,
+};
+
+/// An upstream rename detected during collection.
+pub struct RomRename {
+  pub old_filename: String,
+  pub new_filename: String,
+  /// Package name (without the system basename) used before the rename.
+  pub old_romname: String,
+}
+
+/// Returns true if `source` is the ROM file a state entry was recorded for.
+///
+/// IA and HTTP sources compare the sha1 from item metadata or manifest. Folder sources have no sha1
+/// yet at collection time, so they compare size + mtime — the same signal the
+/// `ComputeHashes` fast-path relies on.
+fn same_rom(source: &RomSourceData, rom_sha1: &str, rom_size: u64, rom_mtime: u64) -> bool {
+  match &source.source {
+    RomSource::InternetArchive(IaSource { sha1, .. })
+    | RomSource::Http(HttpSource { sha1, .. }) => sha1
+      .as_deref()
+      .is_some_and(|s| !s.is_empty() && s.eq_ignore_ascii_case(rom_sha1)),
+    RomSource::Folder(f) => {
+      if rom_size == 0 || rom_mtime == 0 {
+        return false;
+      }
+      let Ok(meta) = fs::metadata(&f.local_path) else {
+        return false;
+      };
+      let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+      meta.len() == rom_size && mtime == rom_mtime
+    }
+  }
+}
+
+/// Detects ROMs that were renamed upstream and migrates their state.
+///
+/// A source is considered renamed when it has no state entry of its own and
+/// exactly one orphan state entry (a filename no longer collected) refers to
+/// the same ROM file. Its state entry is moved to the new filename (its
+/// persisted `romname` is dropped so the name is resolved again from the new
+/// filename). Nothing is touched on disk: see `move_output_dirs`.
+///
+/// Must run before `naming::resolve_romnames`; call `record_replaces`
+/// afterwards.
+pub fn migrate_renames(sources: &[RomSourceData], state: &mut SystemState) -> Vec<RomRename> {
+  let current: HashSet<&str> = sources.iter().map(|s| s.filename.as_str()).collect();
+  let mut claimed: HashSet<String> = HashSet::new();
+  let mut renames = Vec::new();
+
+  for source in sources {
+    if state.roms.contains_key(&source.filename) {
+      continue;
+    }
+    let matches: Vec<&String> = state
+      .roms
+      .iter()
+      .filter(|(filename, _)| !current.contains(filename.as_str()) && !claimed.contains(*filename))
+      .filter(|(_, e)| same_rom(source, &e.rom_sha1, e.rom_size, e.rom_mtime))
+      .map(|(filename, _)| filename)
+      .collect();
+    if matches.len() != 1 {
+      continue;
+    }
+    let old_filename = matches[0].clone();
+
+    let mut entry = state.roms.remove(&old_filename).unwrap();
+    let old_romname = entry
+      .romname
+      .take()
+      .unwrap_or_else(|| normalize_name(&old_filename));
+
+    claimed.insert(old_filename.clone());
+    state.insert(source.filename.clone(), entry);
+    renames.push(RomRename {
+      old_filename,
+      new_filename: source.filename.clone(),
+      old_romname,
+    });
+  }
+
+  renames
+}
+
+/// Records the old package name in `replaces` for every rename whose resolved
+/// `romname` differs from the previous one, so pacman upgrades cleanly.
+pub fn record_replaces(renames: &[RomRename], sources: &[RomSourceData], state: &mut SystemState) {
+  for rename in renames {
+    let Some(source) = sources.iter().find(|s| s.filename == rename.new_filename) else {
+      continue;
+    };
+    if source.romname == rename.old_romname {
+      continue;
+    }
+    if let Some(entry) = state.roms.get_mut(&rename.new_filename) {
+      if !entry.replaces.contains(&rename.old_romname) {
+        entry.replaces.push(rename.old_romname.clone());
+      }
+    }
+  }
+}
+
+/// Moves the output directory of every rename to its new filename and renames
+/// the ROM files inside it, so the next steps find them and skip the download.
+///
+/// The state re-keyed by `migrate_renames` must be saved right after: until
+/// then the state on disk still knows the ROMs by their old names. A run
+/// stopped in between detects the same renames again, and finds the
+/// directories already moved.
+pub fn move_output_dirs(renames: &[RomRename], sources: &[RomSourceData]) {
+  for rename in renames {
+    if let Some(source) = sources.iter().find(|s| s.filename == rename.new_filename) {
+      move_output_dir(&rename.old_filename, source);
+    }
+  }
+}
+
+/// Moves `<old>/` to `<new>/` and renames the ROM files it contains.
+///
+/// Best effort: if the old directory is missing or the new one already
+/// exists, nothing is moved and the regular download/copy steps take over.
+fn move_output_dir(old_filename: &str, source: &RomSourceData) {
+  let old_dir = Path::new(old_filename).with_extension("");
+  let new_dir = Path::new(&source.filename).with_extension("");
+  if old_dir != new_dir && old_dir.is_dir() && !new_dir.exists() {
+    fs::rename(&old_dir, &new_dir).ok();
+  }
+  if new_dir.is_dir() {
+    relocate_rom_files(&new_dir, old_filename, source);
+  }
+}
+
+/// Renames the ROM files in `dir` to the names expected by `source`.
+///
+/// Single-disc ROMs are stored under their own filename. Multi-disc games
+/// store each disc under its real name, which state does not record, so discs
+/// are located by sha1 among the files sharing the disc extension.
+fn relocate_rom_files(dir: &Path, old_filename: &str, source: &RomSourceData) {
+  let disc1_name = source.disc1_filename();
+
+  if source.extra_discs.is_empty() {
+    let old = dir.join(old_filename);
+    let new = dir.join(&disc1_name);
+    if old != new && old.is_file() && !new.exists() {
+      fs::rename(old, new).ok();
+    }
+    return;
+  }
+
+  // Folder sources have no sha1 yet: hash the local files (renames are rare).
+  let sha1_of = |sha1: &Option<String>, local: Option<&Path>| -> Option<String> {
+    sha1
+      .clone()
+      .or_else(|| local.map(|p| hash_file(p, Algorithm::SHA1).to_lowercase()))
+  };
+  let disc1_sha1 = match &source.source {
+    RomSource::InternetArchive(IaSource { sha1, .. })
+    | RomSource::Http(HttpSource { sha1, .. }) => sha1.clone(),
+    RomSource::Folder(f) => sha1_of(&None, Some(&f.local_path)),
+  };
+  let mut expected: Vec<(String, Option<String>)> = vec![(disc1_name, disc1_sha1)];
+  expected.extend(source.extra_discs.iter().map(|d| {
+    (
+      package_file(&d.filename),
+      sha1_of(&d.sha1, d.local_path.as_deref()),
+    )
+  }));
+
+  let ext = Path::new(&source.filename)
+    .extension()
+    .and_then(|e| e.to_str())
+    .unwrap_or("");
+  let candidates: Vec<PathBuf> = match fs::read_dir(dir) {
+    Ok(entries) => entries
+      .filter_map(|e| e.ok().map(|e| e.path()))
+      .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some(ext))
+      .collect(),
+    Err(_) => return,
+  };
+  let hashed: Vec<(PathBuf, String)> = candidates
+    .into_iter()
+    .map(|p| {
+      let sha1 = hash_file(&p, Algorithm::SHA1).to_lowercase();
+      (p, sha1)
+    })
+    .collect();
+
+  for (name, sha1) in expected {
+    let dest = dir.join(&name);
+    if dest.exists() {
+      continue;
+    }
+    let Some(sha1) = sha1 else { continue };
+    if let Some((path, _)) = hashed
+      .iter()
+      .find(|(p, s)| p.exists() && s.eq_ignore_ascii_case(&sha1))
+    {
+      fs::rename(path, &dest).ok();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+  use crate::{rom::FolderSource, state::RomStateEntry};
+
+  fn source(filename: &str, source: RomSource) -> RomSourceData {
+    RomSourceData {
+      file_name: filename.to_string(),
+      filename: filename.to_string(),
+      source,
+      extra_discs: Vec::new(),
+      romname: String::new(),
+      game_dir: None,
+      origin: String::new(),
+    }
+  }
+
+  fn http(filename: &str, sha1: Option<&str>) -> RomSourceData {
+    source(
+      filename,
+      RomSource::Http(HttpSource {
+        rom_url: format!("http://roms.lan/{}", filename),
+        crc32: None,
+        md5: None,
+        sha1: sha1.map(str::to_string),
+        size: 0,
+      }),
+    )
+  }
+
+  fn entry(sha1: &str, romname: Option<&str>) -> RomStateEntry {
+    RomStateEntry {
+      rom_sha1: sha1.to_string(),
+      romname: romname.map(str::to_string),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn sha1_match_ignores_case_and_needs_a_known_sha1() {
+    assert!(same_rom(&http("A.zip", Some("ABCDEF")), "abcdef", 0, 0));
+    assert!(!same_rom(&http("A.zip", Some("abcdef")), "012345", 0, 0));
+    assert!(!same_rom(&http("A.zip", Some("")), "", 0, 0));
+    assert!(!same_rom(&http("A.zip", None), "abcdef", 0, 0));
+  }
+
+  #[test]
+  fn folder_match_compares_size_and_mtime() {
+    let path = std::env::temp_dir().join(format!("rompom-rename-{}.zip", std::process::id()));
+    fs::write(&path, b"rom data").unwrap();
+    let mtime = fs::metadata(&path)
+      .unwrap()
+      .modified()
+      .unwrap()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_secs();
+    let rom = source(
+      "A.zip",
+      RomSource::Folder(FolderSource {
+        local_path: path.clone(),
+        rel_path: "A.zip".to_string(),
+        mirror: None,
+      }),
+    );
+    let same = same_rom(&rom, "", 8, mtime);
+    let other_size = same_rom(&rom, "", 9, mtime);
+    let other_mtime = same_rom(&rom, "", 8, mtime + 1);
+    let unknown = same_rom(&rom, "", 0, 0);
+    fs::remove_file(&path).ok();
+    assert!(same);
+    assert!(!other_size);
+    assert!(!other_mtime);
+    assert!(!unknown);
+  }
+
+  #[test]
+  fn rename_moves_the_state_entry_of_the_only_match() {
+    let mut state = SystemState::default();
+    state.insert("Old.zip".to_string(), entry("abc", Some("old")));
+    state.insert("Kept.zip".to_string(), entry("abc", None));
+    let sources = [http("New.zip", Some("abc")), http("Kept.zip", Some("abc"))];
+
+    let renames = migrate_renames(&sources, &mut state);
+
+    assert_eq!(renames.len(), 1);
+    assert_eq!(renames[0].old_filename, "Old.zip");
+    assert_eq!(renames[0].new_filename, "New.zip");
+    assert_eq!(renames[0].old_romname, "old");
+    assert!(!state.roms.contains_key("Old.zip"));
+    assert_eq!(state.roms["New.zip"].romname, None);
+  }
+
+  #[test]
+  fn rename_is_skipped_when_several_entries_match() {
+    let mut state = SystemState::default();
+    state.insert("Old (v1).zip".to_string(), entry("abc", None));
+    state.insert("Old (v2).zip".to_string(), entry("abc", None));
+
+    let renames = migrate_renames(&[http("New.zip", Some("abc"))], &mut state);
+
+    assert!(renames.is_empty());
+    assert!(state.roms.contains_key("Old (v1).zip"));
+    assert!(state.roms.contains_key("Old (v2).zip"));
+    assert!(!state.roms.contains_key("New.zip"));
+  }
+}
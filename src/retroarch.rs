@@ -0,0 +1,84 @@
+use std::{fs, io, path::Path};
+
+use serde_json::json;
+
+use crate::{
+  conf::RetroArchExport,
+  frontend::{link_or_copy, FrontendEntry},
+};
+
+/// rompom media kind → RetroArch thumbnail folder. RetroArch only displays
+/// PNG thumbnails, other formats are skipped.
+const THUMBNAILS: &[(&str, &str)] = &[
+  ("thumbnail", "Named_Boxarts"),
+  ("screenshot", "Named_Snaps"),
+  ("image", "Named_Titles"),
+];
+
+/// Replaces the characters RetroArch substitutes in thumbnail filenames.
+fn thumbnail_name(label: &str) -> String {
+  label
+    .chars()
+    .map(|c| if "&*/:`<>?\\|\"".contains(c) { '_' } else { c })
+    .collect()
+}
+
+/// Writes `playlists/<playlist>.lpl` under `root` and the matching
+/// `thumbnails/<playlist>/Named_*/<label>.png`.
+///
+/// Entries carry the ROM crc32 so RetroArch matches them against its
+/// databases without rescanning; ROMs with an unknown crc32 fall back to
+/// `DETECT`. Returns the number of games written.
+pub fn export(root: &Path, conf: &RetroArchExport, entries: &[FrontendEntry]) -> io::Result<usize> {
+  let playlists = root.join("playlists");
+  let thumbnails = root.join("thumbnails").join(&conf.playlist);
+  fs::create_dir_all(&playlists)?;
+
+  let db_name = format!("{}.lpl", conf.playlist);
+  let core_path = conf.core_path.as_deref().unwrap_or("DETECT");
+  let core_name = conf.core_name.as_deref().unwrap_or("DETECT");
+
+  let mut items = Vec::new();
+  for entry in entries {
+    let label = &entry.game.name;
+    items.push(json!({
+      "path": format!("{}/{}", conf.rom_path.trim_end_matches('/'), entry.rom_path()),
+      "label": label,
+      "core_path": core_path,
+      "core_name": core_name,
+      "crc32": entry
+        .crc32
+        .as_ref()
+        .map(|c| format!("{}|crc", c.to_uppercase()))
+        .unwrap_or_else(|| "DETECT".to_string()),
+      "db_name": db_name,
+    }));
+
+    for &(kind, folder) in THUMBNAILS {
+      let Some(src) = entry
+        .media(kind)
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("png"))
+      else {
+        continue;
+      };
+      let dest = thumbnails
+        .join(folder)
+        .join(format!("{}.png", thumbnail_name(label)));
+      link_or_copy(&src, &dest)?;
+    }
+  }
+
+  let playlist = json!({
+    "version": "1.5",
+    "default_core_path": conf.core_path.as_deref().unwrap_or(""),
+    "default_core_name": conf.core_name.as_deref().unwrap_or(""),
+    "label_display_mode": 0,
+    "right_thumbnail_mode": 0,
+    "left_thumbnail_mode": 0,
+    "sort_mode": 0,
+    "items": items,
+  });
+  let json = serde_json::to_string_pretty(&playlist).map_err(io::Error::other)?;
+  fs::write(playlists.join(&db_name), json)?;
+  Ok(entries.len())
+}
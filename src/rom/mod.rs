@@ -1,7 +1,7 @@
 mod source;
 mod step;
 
-pub use source::{DiscFile, FolderSource, IaSource, RomSource, RomSourceData};
+pub use source::{DiscFile, FolderSource, HttpSource, IaSource, RomSource, RomSourceData};
 pub use step::{Step, StepData, StepKind, StepStatus};
 
 use std::sync::{Arc, Mutex};
@@ -151,7 +151,7 @@ impl Rom {
     }))
   }
 
-  // ── Internet Archive pipeline ─────────────────────────────────────────
+  // ── Internet Archive / HTTP pipeline ──────────────────────────────────
   //
   // Index  Kind            wait_for  next
   // ─────  ──────────────  ────────  ────
@@ -162,15 +162,27 @@ impl Rom {
   //   4    DownloadMedias     1      [5]
   //   5    SaveState          2      []
   //
-  /// Builds a ROM with the Internet Archive pipeline and returns it wrapped in
+  /// Builds a ROM with the Internet Archive pipeline (also used by HTTP
+  /// sources, whose hashes come from the manifest) and returns it wrapped in
   /// `Arc<Mutex<_>>`. The caller must enqueue step 0 into the `TaskQueue`.
   pub fn new_ia(source: RomSourceData, bar: RomBar) -> Arc<Mutex<Self>> {
-    // Seed shared hash fields from the IA metadata that was already fetched.
+    // Seed shared hash fields from the IA metadata / HTTP manifest already fetched.
     let (sha1, md5, crc32, size) = match &source.source {
-      RomSource::InternetArchive(ia) => {
-        (ia.sha1.clone(), ia.md5.clone(), ia.crc32.clone(), ia.size)
-      }
-      _ => unreachable!("new_ia called with non-IA source"),
+      RomSource::InternetArchive(IaSource {
+        sha1,
+        md5,
+        crc32,
+        size,
+        ..
+      })
+      | RomSource::Http(HttpSource {
+        sha1,
+        md5,
+        crc32,
+        size,
+        ..
+      }) => (sha1.clone(), md5.clone(), crc32.clone(), *size),
+      _ => unreachable!("new_ia called with a folder source"),
     };
 
     let pipeline = vec![
@@ -18,12 +18,27 @@ pub struct IaSource {
 /// Source-specific data for a local folder ROM.
 pub struct FolderSource {
   pub local_path: PathBuf,
+  /// Path relative to the source folder, `/`-separated.
+  pub rel_path: String,
+  /// Mirror base URL of the source folder, if any.
+  pub mirror: Option<String>,
 }
 
-/// Discriminated union of the two supported ROM sources.
+/// Source-specific data for a ROM served over HTTP (index page or manifest).
+pub struct HttpSource {
+  pub rom_url: String,
+  pub crc32: Option<String>,
+  pub md5: Option<String>,
+  /// SHA-1 from the manifest or checksum file, verified after download.
+  pub sha1: Option<String>,
+  pub size: u64,
+}
+
+/// Discriminated union of the supported ROM sources.
 pub enum RomSource {
   InternetArchive(IaSource),
   Folder(FolderSource),
+  Http(HttpSource),
 }
 
 /// One disc in a multi-disc game (disc 2 and above).
@@ -61,4 +76,30 @@ pub struct RomSourceData {
   pub source: RomSource,
   /// Extra disc files (disc 2, 3, …).  Empty for single-disc games.
   pub extra_discs: Vec<DiscFile>,
+  /// Collision-free normalised package name (without the system basename).
+  /// Empty until `naming::resolve_romnames` runs at the end of collection.
+  pub romname: String,
+  /// Folder name when the ROM is a whole game folder (folder source with
+  /// `game_dir_depth`): `file_name` is its main file, `extra_discs` holds the
+  /// other files, and the package installs them together in that folder.
+  pub game_dir: Option<String>,
+  /// Configured source the ROM was collected from (`internet_archive:<item>`
+  /// or `folder:<path>`), recorded in state to detect source changes.
+  pub origin: String,
+}
+
+impl RomSourceData {
+  /// Local filename of disc 1 in the output directory: the logical filename
+  /// for single-disc games and game folders (which may be a canonical DAT
+  /// name differing from the source), the actual disc-1 basename for
+  /// multi-disc groups.
+  pub fn disc1_filename(&self) -> String {
+    if self.extra_discs.is_empty() || self.game_dir.is_some() {
+      return self.filename.clone();
+    }
+    std::path::Path::new(&self.file_name)
+      .file_name()
+      .map(|n| n.to_string_lossy().into_owned())
+      .unwrap_or_else(|| self.filename.clone())
+  }
 }
@@ -0,0 +1,293 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt, fs, io, path::Path};
+
+use crate::{
+  conf::{Exclusion, OneGameOneRom},
+  dat::Dat,
+  disc_indicator,
+  rom::RomSourceData,
+};
+
+/// Region names used in No-Intro / Redump tags, e.g. `(Japan, USA)`.
+const REGIONS: &[&str] = &[
+  "World",
+  "USA",
+  "Europe",
+  "Japan",
+  "Asia",
+  "Australia",
+  "Austria",
+  "Belgium",
+  "Brazil",
+  "Canada",
+  "China",
+  "Denmark",
+  "Finland",
+  "France",
+  "Germany",
+  "Greece",
+  "Hong Kong",
+  "India",
+  "Italy",
+  "Korea",
+  "Latin America",
+  "Mexico",
+  "Netherlands",
+  "Norway",
+  "Poland",
+  "Portugal",
+  "Russia",
+  "Scandinavia",
+  "Spain",
+  "Sweden",
+  "Switzerland",
+  "Taiwan",
+  "UK",
+  "Unknown",
+];
+
+/// Tags marking each excluded category (`(Beta)`, `(Beta 2)`, …).
+const EXCLUSION_TAGS: &[(Exclusion, &[&str])] = &[
+  (Exclusion::Beta, &["Beta"]),
+  (Exclusion::Proto, &["Proto"]),
+  (Exclusion::Demo, &["Demo", "Sample", "Trial"]),
+  (Exclusion::Unlicensed, &["Unl"]),
+  (Exclusion::Pirate, &["Pirate"]),
+  (Exclusion::Aftermarket, &["Aftermarket", "Homebrew"]),
+  (Exclusion::Kiosk, &["Kiosk"]),
+];
+
+/// Why a file was left out of the collection.
+pub enum DropReason {
+  Excluded(Exclusion),
+  /// Another variant of the same game was preferred.
+  Superseded(String),
+}
+
+impl fmt::Display for DropReason {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      DropReason::Excluded(e) => write!(f, "excluded ({})", format!("{:?}", e).to_lowercase()),
+      DropReason::Superseded(by) => write!(f, "superseded by {}", by),
+    }
+  }
+}
+
+/// Outcome of the 1G1R selection.
+#[derive(Default)]
+pub struct SelectionReport {
+  /// Games kept (one variant each).
+  pub games: usize,
+  /// `(filename, reason)` of every dropped file.
+  pub dropped: Vec<(String, DropReason)>,
+}
+
+/// Title and tags of a No-Intro style name:
+/// `Super Mario World (USA) (Rev 1)` → `Super Mario World`, `[USA, Rev 1]`.
+struct Parsed {
+  title: String,
+  tags: Vec<String>,
+}
+
+fn parse_name(stem: &str) -> Parsed {
+  let cut = [stem.find(" ("), stem.find(" [")]
+    .into_iter()
+    .flatten()
+    .min()
+    .unwrap_or(stem.len());
+  let mut tags = Vec::new();
+  let mut rest = &stem[cut..];
+  while let Some(start) = rest.find('(') {
+    let Some(len) = rest[start..].find(')') else {
+      break;
+    };
+    tags.push(rest[start + 1..start + len].to_string());
+    rest = &rest[start + len + 1..];
+  }
+  Parsed {
+    title: stem[..cut].trim().to_string(),
+    tags,
+  }
+}
+
+/// Regions of a tag like `Japan, USA`, `None` if it is not a region tag.
+fn regions(tag: &str, extra: &[String]) -> Option<Vec<String>> {
+  let parts: Vec<&str> = tag.split(", ").collect();
+  parts
+    .iter()
+    .all(|p| REGIONS.contains(p) || extra.iter().any(|e| e.eq_ignore_ascii_case(p)))
+    .then(|| parts.iter().map(|p| p.to_string()).collect())
+}
+
+/// Languages of a tag like `En,Fr,De` or `En-US`, `None` otherwise.
+fn languages(tag: &str) -> Option<Vec<String>> {
+  let is_lang = |l: &str| {
+    let b = l.as_bytes();
+    matches!(b.len(), 2 | 5)
+      && b[0].is_ascii_uppercase()
+      && b[1].is_ascii_lowercase()
+      && (b.len() == 2 || b[2] == b'-')
+  };
+  let parts: Vec<&str> = tag.split(',').collect();
+  parts
+    .iter()
+    .all(|l| is_lang(l))
+    .then(|| parts.iter().map(|l| l.to_string()).collect())
+}
+
+/// Revision of a `Rev 1` / `Rev A` / `v1.1` tag; files without revision
+/// rank lowest.
+fn revision(tag: &str) -> Option<Vec<u32>> {
+  if let Some(rev) = tag.strip_prefix("Rev ") {
+    return match rev.parse::<u32>() {
+      Ok(n) => Some(vec![n]),
+      Err(_) => rev
+        .chars()
+        .next()
+        .filter(|c| c.is_ascii_uppercase())
+        .map(|c| vec![c as u32 - 'A' as u32 + 1]),
+    };
+  }
+  let version = tag.strip_prefix('v')?;
+  version.split('.').map(|n| n.parse::<u32>().ok()).collect()
+}
+
+fn exclusion(tag: &str) -> Option<Exclusion> {
+  EXCLUSION_TAGS.iter().find_map(|(category, prefixes)| {
+    prefixes
+      .iter()
+      .any(|p| tag == *p || tag.strip_prefix(p).is_some_and(|r| r.starts_with(' ')))
+      .then_some(*category)
+  })
+}
+
+/// One variant of a game: a single file, or every disc of a multi-disc dump.
+struct Variant {
+  /// Filename without disc indicator, shared by the discs.
+  name: String,
+  indexes: Vec<usize>,
+  parsed: Parsed,
+}
+
+impl Variant {
+  /// Sort key, lower is better: region rank, language rank, newest revision,
+  /// fewest extra tags, then name for a stable choice.
+  fn rank(&self, conf: &OneGameOneRom) -> (usize, usize, Reverse<Vec<u32>>, usize, String) {
+    let position = |list: &[String], values: &[String]| {
+      values
+        .iter()
+        .filter_map(|v| list.iter().position(|l| l.eq_ignore_ascii_case(v)))
+        .min()
+        .unwrap_or(list.len())
+    };
+    let mut region = conf.regions.len();
+    let mut language = conf.languages.len();
+    let mut rev = Vec::new();
+    let mut other = 0;
+    for tag in &self.parsed.tags {
+      if let Some(r) = regions(tag, &conf.regions) {
+        region = region.min(position(&conf.regions, &r));
+      } else if let Some(l) = languages(tag) {
+        language = language.min(position(&conf.languages, &l));
+      } else if let Some(v) = revision(tag) {
+        rev = v;
+      } else {
+        other += 1;
+      }
+    }
+    (region, language, Reverse(rev), other, self.name.clone())
+  }
+}
+
+/// Keeps one variant per game among `sources`.
+///
+/// Files are grouped by DAT parent set when a DAT knows them, by title
+/// otherwise. Variants carrying an excluded tag are dropped first; the best
+/// remaining variant of each group (see `Variant::rank`) is kept with all its
+/// discs. Runs before multi-disc grouping.
+pub fn select(
+  sources: &mut Vec<RomSourceData>,
+  conf: &OneGameOneRom,
+  dat: Option<&Dat>,
+) -> SelectionReport {
+  let mut variants: BTreeMap<String, Variant> = BTreeMap::new();
+  for (i, source) in sources.iter().enumerate() {
+    let path = Path::new(&source.filename);
+    let stem = path
+      .file_stem()
+      .and_then(|s| s.to_str())
+      .unwrap_or(&source.filename);
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let base = disc_indicator(stem).map_or(stem.to_string(), |(base, _)| base);
+    variants
+      .entry(format!("{}.{}", base, ext))
+      .or_insert_with(|| Variant {
+        name: format!("{}.{}", base, ext)
+          .trim_end_matches('.')
+          .to_string(),
+        indexes: Vec::new(),
+        parsed: parse_name(&base),
+      })
+      .indexes
+      .push(i);
+  }
+
+  let mut report = SelectionReport::default();
+  let mut drop = vec![false; sources.len()];
+  let mut groups: BTreeMap<String, Vec<Variant>> = BTreeMap::new();
+  for variant in variants.into_values() {
+    let excluded = variant
+      .parsed
+      .tags
+      .iter()
+      .filter_map(|t| exclusion(t))
+      .find(|c| conf.exclude.contains(c));
+    if let Some(category) = excluded {
+      for &i in &variant.indexes {
+        drop[i] = true;
+        report
+          .dropped
+          .push((sources[i].filename.clone(), DropReason::Excluded(category)));
+      }
+      continue;
+    }
+    let first = &sources[variant.indexes[0]].filename;
+    let key = dat
+      .and_then(|d| d.parent(first))
+      .map(str::to_string)
+      .unwrap_or_else(|| variant.parsed.title.to_lowercase());
+    groups.entry(key).or_default().push(variant);
+  }
+
+  for mut group in groups.into_values() {
+    group.sort_by_cached_key(|v| v.rank(conf));
+    let best = group.remove(0);
+    for variant in group {
+      for &i in &variant.indexes {
+        drop[i] = true;
+        report.dropped.push((
+          sources[i].filename.clone(),
+          DropReason::Superseded(best.name.clone()),
+        ));
+      }
+    }
+    report.games += 1;
+  }
+
+  let mut index = 0;
+  sources.retain(|_| {
+    index += 1;
+    !drop[index - 1]
+  });
+  report.dropped.sort_by(|a, b| a.0.cmp(&b.0));
+  report
+}
+
+/// Writes the dropped files and their reason.
+pub fn write_report(path: &str, report: &SelectionReport) -> io::Result<()> {
+  let out: String = report
+    .dropped
+    .iter()
+    .map(|(filename, reason)| format!("{}  {}\n", filename, reason))
+    .collect();
+  fs::write(path, out)
+}
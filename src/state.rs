@@ -12,15 +12,44 @@ pub struct RomStateEntry {
   /// Taille du fichier ROM en octets. 0 = non renseigné.
   #[serde(default)]
   pub rom_size: u64,
+  /// crc32 de la ROM, pour les playlists RetroArch. `None` si inconnu.
+  #[serde(default)]
+  pub rom_crc32: Option<String>,
+  /// md5 de la ROM, pour le champ `md5` de `description.xml`. `None` si inconnu.
+  #[serde(default)]
+  pub rom_md5: Option<String>,
   pub medias: HashMap<String, Option<String>>,
   /// SHA-1 hashes for extra discs (disc 2, 3, …).  Empty for single-disc ROMs.
   #[serde(default)]
   pub extra_disc_sha1s: Vec<String>,
+  /// Normalised package name chosen for this ROM (after collision handling).
+  /// Kept so the pkgname stays stable across runs. `None` for old entries.
+  #[serde(default)]
+  pub romname: Option<String>,
+  /// Previous package names (without basename) of this ROM, from upstream
+  /// renames. Emitted as `replaces=()` / `conflicts=()` in the PKGBUILD.
+  #[serde(default)]
+  pub replaces: Vec<String>,
+  /// Dossier du jeu quand la ROM est un dossier-jeu (`game_dir_depth`).
+  #[serde(default)]
+  pub game_dir: Option<String>,
+  /// Autres fichiers du dossier-jeu, installés avec le fichier principal.
+  #[serde(default)]
+  pub game_files: Vec<String>,
+  /// Source d'origine (`internet_archive:<item>` ou `folder:<chemin>`).
+  /// `None` pour les anciennes entrées.
+  #[serde(default)]
+  pub origin: Option<String>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SystemState {
   pub roms: HashMap<String, RomStateEntry>,
+  /// Empreinte des sources, de la configuration et des modèles lors du
+  /// dernier run complet sans erreur (`fingerprint::compute`). `None` après un
+  /// run interrompu ou en échec.
+  #[serde(default)]
+  pub fingerprint: Option<String>,
 }
 
 impl SystemState {
@@ -1,4 +1,16 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
+
+use crate::{
+  collect,
+  dat::DatReport,
+  duplicates::{Duplicate, OriginChange},
+  frontend::FrontendExport,
+  naming::NameCollision,
+  orphans::Orphan,
+  rename::RomRename,
+  selection::SelectionReport,
+  userdata::UserdataReport,
+};
 
 /// End-of-run statistics printed after the TUI exits.
 pub struct Summary {
@@ -11,6 +23,36 @@ pub struct Summary {
   /// Average wall-clock time per step kind, in canonical pipeline order.
   /// Only populated for step kinds that actually ran at least once.
   pub step_avg_durations: Vec<(&'static str, Duration)>,
+  /// Package names that had to be disambiguated during collection.
+  pub name_collisions: Vec<NameCollision>,
+  /// ROMs renamed upstream whose state and output directory were migrated.
+  pub renames: Vec<RomRename>,
+  /// State entries / output dirs whose ROM is no longer in the source.
+  pub orphans: Vec<Orphan>,
+  /// `(path, game count)` of the exported gamelist, with `--gamelist`.
+  pub gamelist: Option<(String, usize)>,
+  /// Frontend exports configured for the system (`exports:`).
+  pub exports: Vec<FrontendExport>,
+  /// `(system directory, report)` of the userdata export, with `--export`.
+  pub userdata: Option<(PathBuf, UserdataReport)>,
+  /// `(report path, report)` of the DAT filtering, when the system has a `dat`.
+  pub dat: Option<(String, DatReport)>,
+  /// `(report path, report)` of the 1G1R selection (`one_game_one_rom`).
+  pub selection: Option<(String, SelectionReport)>,
+  /// `(report path, files)` of the duplicates dropped across sources.
+  pub duplicates: Option<(String, Vec<Duplicate>)>,
+  /// ROMs collected from another source than in the previous run.
+  pub origin_changes: Vec<OriginChange>,
+  /// HTTP index files skipped because no sha1 is known for them.
+  pub unhashed: Vec<String>,
+  /// `(item, network error)` of IA items whose cached metadata was used.
+  pub stale_metadata: Vec<(String, String)>,
+  /// Sources that could not be collected.
+  pub collection_errors: Vec<collect::Error>,
+  /// Files left out of their source.
+  pub skipped: Vec<collect::Skip>,
+  /// Nothing changed since the last successful run: the pipeline was skipped.
+  pub fingerprint_unchanged: bool,
 }
 
 impl Summary {
@@ -20,7 +62,22 @@ impl Summary {
     println!("  =  {:>4}  unchanged", self.unchanged);
     println!("  ✗  {:>4}  errors\n", self.errors);
 
-    if self.success > 0 {
+    if !self.collection_errors.is_empty() {
+      println!("Collection errors (ROMs of these sources left untouched)");
+      for e in &self.collection_errors {
+        println!("  {}", e);
+      }
+      println!();
+    }
+
+    if self.fingerprint_unchanged {
+      println!(
+        "Sources, configuration and templates unchanged since the last run — \
+         nothing to do (--force to run anyway)\n"
+      );
+    }
+
+    if self.success > 0 && !self.fingerprint_unchanged {
       println!("Media coverage");
       for &(kind, icon, found) in &self.media_stats {
         let bar = progress_bar(found, self.success, 20);
@@ -40,6 +97,121 @@ impl Summary {
       }
       println!();
     }
+
+    if let Some((ref path, ref d)) = self.duplicates {
+      println!(
+        "Duplicates: {} file(s) provided by several sources dropped — see {}\n",
+        d.len(),
+        path
+      );
+    }
+
+    if let Some((ref path, ref r)) = self.dat {
+      println!("DAT");
+      println!("  {:>5}  verified", r.verified);
+      println!("  {:>5}  kept by name only", r.unverified);
+      println!("  {:>5}  renamed to their DAT name", r.renamed.len());
+      println!("  {:>5}  bad dumps (skipped)", r.bad.len());
+      println!("  {:>5}  not in DAT (skipped)", r.extra.len());
+      println!("  {:>5}  missing from source", r.missing.len());
+      println!("  details in {}\n", path);
+    }
+
+    if let Some((ref path, ref r)) = self.selection {
+      println!(
+        "1G1R: {} games kept, {} variant file(s) dropped — see {}\n",
+        r.games,
+        r.dropped.len(),
+        path
+      );
+    }
+
+    if !self.name_collisions.is_empty() {
+      println!("Package name collisions");
+      for c in &self.name_collisions {
+        println!("  {}  {} → {}", c.filename, c.base, c.romname);
+      }
+      println!();
+    }
+
+    if !self.stale_metadata.is_empty() {
+      println!("Cached metadata used (could not revalidate)");
+      for (item, error) in &self.stale_metadata {
+        println!("  {}  {}", item, error);
+      }
+      println!();
+    }
+
+    if !self.skipped.is_empty() {
+      println!("Skipped files");
+      for skip in &self.skipped {
+        println!("  {}", skip);
+      }
+      println!();
+    }
+
+    if !self.unhashed.is_empty() {
+      println!("Skipped HTTP files (no sha1 in sha1sums)");
+      for path in &self.unhashed {
+        println!("  {}", path);
+      }
+      println!();
+    }
+
+    if !self.origin_changes.is_empty() {
+      println!("Source changed (repackaged)");
+      for c in &self.origin_changes {
+        println!("  {}  {} → {}", c.filename, c.previous, c.current);
+      }
+      println!();
+    }
+
+    if !self.renames.is_empty() {
+      println!("Renamed upstream");
+      for r in &self.renames {
+        println!("  {} → {}", r.old_filename, r.new_filename);
+      }
+      println!();
+    }
+
+    if !self.orphans.is_empty() {
+      println!("Orphans (no longer in source — see --clean)");
+      for o in &self.orphans {
+        println!("  {}", o.pkgname);
+      }
+      println!();
+    }
+
+    if let Some((ref path, count)) = self.gamelist {
+      println!("Gamelist: {} games written to {}\n", count, path);
+    }
+
+    if !self.exports.is_empty() {
+      println!("Frontend exports");
+      for e in &self.exports {
+        match e.result {
+          Ok(count) => println!(
+            "  {:<10}  {} games → {}",
+            e.frontend,
+            count,
+            e.path.display()
+          ),
+          Err(ref err) => println!("  {:<10}  ✗ {}", e.frontend, err),
+        }
+      }
+      println!();
+    }
+
+    if let Some((ref dir, ref r)) = self.userdata {
+      println!(
+        "Userdata export: {} installed, {} updated, {} removed, {} unchanged → {}\n",
+        r.installed,
+        r.updated,
+        r.removed,
+        r.unchanged,
+        dir.display()
+      );
+    }
   }
 }
 
@@ -442,6 +442,21 @@ impl Ui {
       errors,
       media_stats,
       step_avg_durations: Vec::new(),
+      name_collisions: Vec::new(),
+      renames: Vec::new(),
+      orphans: Vec::new(),
+      gamelist: None,
+      exports: Vec::new(),
+      userdata: None,
+      dat: None,
+      selection: None,
+      duplicates: None,
+      origin_changes: Vec::new(),
+      unhashed: Vec::new(),
+      stale_metadata: Vec::new(),
+      collection_errors: Vec::new(),
+      skipped: Vec::new(),
+      fingerprint_unchanged: false,
     }
   }
 }
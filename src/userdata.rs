@@ -0,0 +1,263 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fs, io,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  conf::System,
+  gamelist,
+  package::{read_pkgver, read_sources, source_file},
+  state::SystemState,
+};
+
+/// Manifest kept in the exported system directory, listing what rompom
+/// installed there so later exports only touch what changed.
+const MANIFEST: &str = ".rompom.yml";
+
+/// Staging directory of the export, inside the system directory so that
+/// installed files are moved into place rather than copied twice.
+const STAGING: &str = ".rompom-staging";
+
+/// Runs `build()` then `package()` of a PKGBUILD the way makepkg does: in
+/// `$srcdir`, with errexit on.
+const RUN_PACKAGE: &str = r#"set -e
+source "$1"
+cd "$srcdir"
+build
+cd "$srcdir"
+package
+"#;
+
+/// Files installed for one ROM.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct InstalledRom {
+  /// `pkgver` of the package directory when it was exported.
+  pkgver: u32,
+  /// `<path>` of its gamelist entry.
+  game_path: String,
+  /// Its gamelist entry, as installed.
+  #[serde(default)]
+  entry: String,
+  /// Installed files, relative to the system directory.
+  files: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+  roms: HashMap<String, InstalledRom>,
+}
+
+/// Outcome of a userdata export, for the end-of-run summary.
+#[derive(Debug, Default)]
+pub struct UserdataReport {
+  pub installed: usize,
+  pub updated: usize,
+  pub removed: usize,
+  pub unchanged: usize,
+}
+
+/// Appends the files under `dir` to `files`, relative to `rel`, sorted.
+fn list_files(dir: &Path, rel: &str, files: &mut Vec<String>) -> io::Result<()> {
+  let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+  entries.sort_by_key(|e| e.file_name());
+  for entry in entries {
+    let name = entry.file_name().to_string_lossy().into_owned();
+    let rel_path = if rel.is_empty() {
+      name
+    } else {
+      format!("{}/{}", rel, name)
+    };
+    if entry.file_type()?.is_dir() {
+      list_files(&entry.path(), &rel_path, files)?;
+    } else {
+      files.push(rel_path);
+    }
+  }
+  Ok(())
+}
+
+/// Runs the `package()` of the PKGBUILD in `package_dir` into `staging`,
+/// makepkg-style: the sources are linked into `staging/src`, the package is
+/// built into `staging/pkg`. The install layout thus comes from the package
+/// templates themselves.
+///
+/// Returns the directory the package fills for `system` (`userdata/roms/<dir>/`
+/// under `staging/pkg`) and its gamelist entry, as adjusted by `package()`.
+fn run_package(
+  system: &System,
+  package_dir: &Path,
+  staging: &Path,
+) -> io::Result<(PathBuf, String)> {
+  let package_dir = fs::canonicalize(package_dir)?;
+  let srcdir = staging.join("src");
+  let pkgdir = staging.join("pkg");
+  fs::create_dir_all(&srcdir)?;
+  fs::create_dir_all(&pkgdir)?;
+  let sources = read_sources(&package_dir)
+    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no PKGBUILD"))?;
+  for source in sources {
+    let name = source_file(&source);
+    let path = package_dir.join(&name);
+    if !path.is_file() {
+      return Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not downloaded", path.display()),
+      ));
+    }
+    std::os::unix::fs::symlink(&path, srcdir.join(&name))?;
+  }
+  let output = Command::new("bash")
+    .arg("-c")
+    .arg(RUN_PACKAGE)
+    .arg("bash")
+    .arg(package_dir.join("PKGBUILD"))
+    .env("srcdir", &srcdir)
+    .env("pkgdir", &pkgdir)
+    .output()?;
+  if !output.status.success() {
+    return Err(io::Error::other(format!(
+      "package() of {} failed: {}",
+      package_dir.display(),
+      String::from_utf8_lossy(&output.stderr).trim()
+    )));
+  }
+  let entry = fs::read_to_string(srcdir.join("description.xml"))?
+    .trim()
+    .to_string();
+  Ok((pkgdir.join("userdata/roms").join(&system.dir), entry))
+}
+
+/// Moves `rel` from `staged` to `root`, replacing what is there.
+fn install_file(staged: &Path, root: &Path, rel: &str) -> io::Result<()> {
+  let dest = root.join(rel);
+  if let Some(parent) = dest.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  fs::rename(staged.join(rel), dest)
+}
+
+/// Removes `rel` from `root`, then its parent directories left empty.
+fn remove_file(root: &Path, rel: &str) {
+  let path = root.join(rel);
+  fs::remove_file(&path).ok();
+  let mut dir = path.parent();
+  while let Some(d) = dir.filter(|d| *d != root) {
+    if fs::remove_dir(d).is_err() {
+      break;
+    }
+    dir = d.parent();
+  }
+}
+
+/// Exports every packaged ROM recorded in state into `roms_root/<dir>/`: what
+/// its package installs in `userdata/roms/<dir>/` (see `run_package`), and its
+/// entry merged into the `gamelist.xml` there.
+///
+/// The export is incremental: a ROM is reinstalled only when its package
+/// `pkgver` changed since the last export or one of its files is missing, and
+/// ROMs that left the state are removed along with their gamelist entry.
+/// Gamelist entries rompom did not write are kept untouched.
+pub fn export(
+  roms_root: &Path,
+  system: &System,
+  state: &SystemState,
+) -> io::Result<UserdataReport> {
+  let root = roms_root.join(&system.dir);
+  fs::create_dir_all(&root)?;
+  let manifest_path = root.join(MANIFEST);
+  let mut manifest: Manifest = fs::read_to_string(&manifest_path)
+    .ok()
+    .and_then(|s| serde_yaml::from_str(&s).ok())
+    .unwrap_or_default();
+
+  let mut report = UserdataReport::default();
+  let mut entries: Vec<String> = Vec::new();
+  // Gamelist paths to replace or drop (ours, current and previous).
+  let mut owned_paths: HashSet<String> = HashSet::new();
+
+  let staging = root.join(STAGING);
+  let mut filenames: Vec<&String> = state.roms.keys().collect();
+  filenames.sort();
+  for filename in filenames {
+    let package_dir = Path::new(filename).with_extension("");
+    if !package_dir.join("PKGBUILD").is_file() {
+      continue; // not packaged yet
+    }
+    let pkgver = read_pkgver(&package_dir);
+
+    let previous = manifest.roms.get(filename);
+    if let Some(p) = previous {
+      owned_paths.insert(p.game_path.clone());
+    }
+    let up_to_date = previous.is_some_and(|p| {
+      p.pkgver == pkgver && !p.entry.is_empty() && p.files.iter().all(|f| root.join(f).exists())
+    });
+    if let Some(p) = previous.filter(|_| up_to_date) {
+      entries.push(p.entry.clone());
+      report.unchanged += 1;
+      continue;
+    }
+
+    if staging.exists() {
+      fs::remove_dir_all(&staging)?;
+    }
+    let (staged, entry) = run_package(system, &package_dir, &staging)?;
+    let mut files = Vec::new();
+    if staged.is_dir() {
+      list_files(&staged, "", &mut files)?;
+    }
+    for file in &files {
+      install_file(&staged, &root, file)?;
+    }
+    fs::remove_dir_all(&staging)?;
+
+    let game_path = gamelist::game_path(&entry).unwrap_or_default();
+    owned_paths.insert(game_path.clone());
+    entries.push(entry.clone());
+    if let Some(p) = previous {
+      for stale in p.files.iter().filter(|f| !files.contains(f)) {
+        remove_file(&root, stale);
+      }
+      report.updated += 1;
+    } else {
+      report.installed += 1;
+    }
+    manifest.roms.insert(
+      filename.clone(),
+      InstalledRom {
+        pkgver,
+        game_path,
+        entry,
+        files,
+      },
+    );
+  }
+
+  let removed: Vec<String> = manifest
+    .roms
+    .keys()
+    .filter(|f| !state.roms.contains_key(*f))
+    .cloned()
+    .collect();
+  for filename in removed {
+    let installed = manifest.roms.remove(&filename).unwrap();
+    for file in &installed.files {
+      remove_file(&root, file);
+    }
+    owned_paths.insert(installed.game_path);
+    report.removed += 1;
+  }
+
+  let gamelist_path = root.join("gamelist.xml");
+  let existing = fs::read_to_string(&gamelist_path).unwrap_or_default();
+  let merged = gamelist::merge_gamelist(&existing, &owned_paths, &entries);
+  fs::write(&gamelist_path, merged)?;
+
+  let yaml = serde_yaml::to_string(&manifest).map_err(io::Error::other)?;
+  fs::write(&manifest_path, yaml)?;
+  Ok(report)
+}
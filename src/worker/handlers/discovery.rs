@@ -7,6 +7,7 @@ use std::{
 use checksums::{hash_file, Algorithm};
 
 use crate::{
+  conf::GameField,
   rom::{Rom, RomSource, StepData, StepKind, StepStatus},
   ui::{ModalCandidate, ModalRequest, ModalResponse},
 };
@@ -18,6 +19,15 @@ use super::super::{
 
 // ── ComputeHashes ─────────────────────────────────────────────────────────
 
+/// Hashes restored from the saved state by the ComputeHashes fast-path.
+struct CachedHashes {
+  sha1: String,
+  crc32: Option<String>,
+  md5: Option<String>,
+  mtime: u64,
+  size: u64,
+}
+
 /// Compute SHA-1/MD5/CRC-32 for a folder-source ROM.
 ///
 /// Fast-skip: if the saved state has a matching mtime + size for this file,
@@ -46,7 +56,7 @@ pub(crate) fn handle_compute_hashes(
   rom_arc.lock().unwrap().bar.discovering();
 
   // ── Fast-skip: check mtime + size against saved state ─────────────────
-  let fast_result: Option<(String, u64, u64)> = {
+  let fast_result: Option<CachedHashes> = {
     let state = ctx.state.lock().unwrap();
     state.roms.get(&filename).and_then(|entry| {
       if entry.rom_mtime == 0 {
@@ -59,19 +69,45 @@ pub(crate) fn handle_compute_hashes(
         .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
         .map(|d| d.as_secs())?;
       if mtime == entry.rom_mtime && meta.len() == entry.rom_size {
-        Some((entry.rom_sha1.clone(), mtime, meta.len()))
+        Some(CachedHashes {
+          sha1: entry.rom_sha1.clone(),
+          crc32: entry.rom_crc32.clone(),
+          md5: entry.rom_md5.clone(),
+          mtime,
+          size: meta.len(),
+        })
       } else {
         None
       }
     })
   };
 
-  if let Some((sha1, mtime, size)) = fast_result {
+  if let Some(CachedHashes {
+    sha1,
+    crc32,
+    md5,
+    mtime,
+    size,
+  }) = fast_result
+  {
+    // crc32 is kept for the RetroArch export; entries saved before it was
+    // recorded get it computed once.
+    let crc32 = crc32.unwrap_or_else(|| hash_file(&local_path, Algorithm::CRC32).to_lowercase());
+    // md5 is only needed by the optional `md5` description field —
+    // jeuinfo_by_gameid won't need it (cached game_id).
+    let md5 = md5.or_else(|| {
+      ctx
+        .system
+        .extra_fields
+        .contains(&GameField::Md5)
+        .then(|| hash_file(&local_path, Algorithm::MD5).to_lowercase())
+    });
     let mut rom = rom_arc.lock().unwrap();
     rom.sha1 = Some(sha1.clone());
     rom.mtime = mtime;
     rom.size = size;
-    // md5/crc32 stay None — jeuinfo_by_gameid won't need them (cached game_id)
+    rom.md5 = md5;
+    rom.crc32 = Some(crc32);
     rom.debug_log.push(format!(
       "[ComputeHashes] fast-path: HIT  (mtime={}, size={}, sha1={})",
       mtime, size, sha1
@@ -170,23 +206,26 @@ pub(crate) fn handle_lookup_ss(
   ctx: &WorkerContext,
 ) -> Result<StepStatus, String> {
   // ── Read source data from rom (release lock before network calls) ──────
-  let (filename, sha1, md5, crc32, size, is_ia_source) = {
+  let (filename, sha1, md5, crc32, size, is_remote_source) = {
     let rom = rom_arc.lock().unwrap();
-    let is_ia = matches!(rom.source.source, RomSource::InternetArchive(_));
+    let is_remote = matches!(
+      rom.source.source,
+      RomSource::InternetArchive(_) | RomSource::Http(_)
+    );
     (
       rom.source.filename.clone(),
       rom.sha1.clone(),
       rom.md5.clone(),
       rom.crc32.clone(),
       rom.size,
-      is_ia,
+      is_remote,
     )
   };
 
   rom_arc.lock().unwrap().bar.discovering();
 
-  // ── For IA sources: determine rom_unchanged here (no ComputeHashes ran) ─
-  if is_ia_source {
+  // ── For IA / HTTP sources: determine rom_unchanged here (no ComputeHashes ran)
+  if is_remote_source {
     // Collect extra-disc sha1s from the source (seeded at collection time).
     let extra_disc_sha1s_current: Vec<String> = {
       let rom = rom_arc.lock().unwrap();
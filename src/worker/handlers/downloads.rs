@@ -5,11 +5,18 @@ use std::{
 };
 
 use checksums::{hash_file, Algorithm};
-use internet_archive::download::{Download, DownloadMethod};
+use internet_archive::metadata::Metadata;
 
-use crate::rom::{Rom, RomSource, StepStatus};
+use crate::{
+  http,
+  package::package_file,
+  rom::{Rom, RomSource, StepStatus},
+};
 
-use super::super::{helpers::media_filename, WorkerContext};
+use super::super::{
+  helpers::{media_filename, stale_media_files},
+  WorkerContext,
+};
 
 // ── CopyRom ───────────────────────────────────────────────────────────────
 
@@ -29,6 +36,13 @@ pub(crate) fn handle_copy_rom(
       _ => unreachable!("CopyRom only runs on folder sources"),
     };
     // Extra disc local paths and sha1s (computed by ComputeHashes).
+    if rom.extra_disc_sha1s.len() != rom.source.extra_discs.len() {
+      return Err(format!(
+        "{} extra file(s) but {} sha1(s) computed",
+        rom.source.extra_discs.len(),
+        rom.extra_disc_sha1s.len()
+      ));
+    }
     let extra_discs: Vec<(PathBuf, String, String)> = rom
       .source
       .extra_discs
@@ -94,7 +108,7 @@ pub(crate) fn handle_copy_rom(
 
   // ── Extra discs (disc 2, 3, …) ────────────────────────────────────────
   for (extra_local, extra_filename, extra_sha1) in &extra_discs {
-    let dest = directory.join(extra_filename);
+    let dest = directory.join(package_file(extra_filename));
     copy_disc(extra_local, &dest, extra_sha1)?;
   }
 
@@ -103,34 +117,58 @@ pub(crate) fn handle_copy_rom(
 
 // ── DownloadRom ───────────────────────────────────────────────────────────
 
-/// Download a ROM from Internet Archive to its output directory.
+/// Every URL Internet Archive serves `name` from, in the order the metadata
+/// gives them; `fallback` (the URL chosen at collection) if it lists none.
+fn ia_urls(metadata: &Metadata, name: &str, fallback: &str) -> Vec<String> {
+  match metadata.file_urls(name) {
+    Ok(urls) if !urls.is_empty() => urls,
+    _ => vec![fallback.to_string()],
+  }
+}
+
+/// Download a ROM from Internet Archive or an HTTP source to its output
+/// directory.
 ///
 /// For multi-disc games, downloads all disc files (disc 1 from the main
-/// source, discs 2+ from `extra_discs`).
+/// source, discs 2+ from `extra_discs`). IA files rotate across the item's
+/// mirrors; interrupted downloads resume (see `http::download`).
 pub(crate) fn handle_download_rom(
   rom_arc: &Arc<Mutex<Rom>>,
   _step_idx: usize,
   _ctx: &WorkerContext,
 ) -> Result<StepStatus, String> {
-  let (filename, file_name_in_item, metadata, rom_unchanged, extra_discs) = {
+  let (filename, rom_unchanged, files) = {
     let rom = rom_arc.lock().unwrap();
-    let (metadata, file_name) = match &rom.source.source {
-      RomSource::InternetArchive(ia) => (Arc::clone(&ia.metadata), rom.source.file_name.clone()),
-      _ => unreachable!("DownloadRom only runs on IA sources"),
+    let disc1 = rom.source.disc1_filename();
+    // (mirror URLs, local name, sha1) of disc 1 then discs 2+.
+    let files: Vec<(Vec<String>, String, Option<String>)> = match &rom.source.source {
+      RomSource::InternetArchive(ia) => std::iter::once((
+        ia_urls(&ia.metadata, &rom.source.file_name, &ia.rom_url),
+        disc1,
+        ia.sha1.clone(),
+      ))
+      .chain(rom.source.extra_discs.iter().map(|d| {
+        (
+          ia_urls(&ia.metadata, &d.file_name, &d.rom_url),
+          d.filename.clone(),
+          d.sha1.clone(),
+        )
+      }))
+      .collect(),
+      RomSource::Http(http) => {
+        std::iter::once((vec![http.rom_url.clone()], disc1, http.sha1.clone()))
+          .chain(
+            rom
+              .source
+              .extra_discs
+              .iter()
+              .map(|d| (vec![d.rom_url.clone()], d.filename.clone(), d.sha1.clone())),
+          )
+          .collect()
+      }
+      RomSource::Folder(_) => unreachable!("DownloadRom only runs on IA and HTTP sources"),
     };
-    let extra_discs: Vec<(String, String)> = rom
-      .source
-      .extra_discs
-      .iter()
-      .map(|d| (d.file_name.clone(), d.filename.clone()))
-      .collect();
-    (
-      rom.source.filename.clone(),
-      file_name,
-      metadata,
-      rom.rom_unchanged,
-      extra_discs,
-    )
+    (rom.source.filename.clone(), rom.rom_unchanged, files)
   };
 
   // Output directory derived from the logical/virtual filename.
@@ -142,50 +180,31 @@ pub(crate) fn handle_download_rom(
     return Ok(StepStatus::Done);
   }
 
-  // ── Disc 1 ────────────────────────────────────────────────────────────
-  // Derive the actual local filename from the IA path (handles multi-disc
-  // where the virtual `filename` differs from the disc-1 basename).
-  let disc1_local_name = Path::new(&file_name_in_item)
-    .file_name()
-    .map(|n| n.to_string_lossy().into_owned())
-    .unwrap_or_else(|| filename.clone());
-  let dest1 = directory.join(&disc1_local_name);
-
-  let dl1 = Download::new(&metadata, &file_name_in_item).map_err(|e| e.to_string())?;
-  if dest1.exists() {
-    rom_arc.lock().unwrap().bar.rom_checking();
-    match dl1.verify_sha1(&dest1) {
-      Ok(()) => {
-        rom_arc.lock().unwrap().bar.rom_skipped();
-      }
-      Err(_) => {
-        rom_arc.lock().unwrap().bar.rom_redownloading();
-        dl1
-          .fetch(&dest1, DownloadMethod::Https)
-          .map_err(|e| e.to_string())?;
-        dl1.verify_sha1(&dest1).map_err(|e| e.to_string())?;
-        rom_arc.lock().unwrap().bar.rom_done();
+  // Files are saved under their local name (see
+  // `RomSourceData::disc1_filename`), which may differ from the source path
+  // for multi-disc groups and DAT-renamed ROMs.
+  let mut fetched = false;
+  for (urls, local_name, sha1) in &files {
+    let dest = directory.join(local_name);
+    if dest.exists() {
+      rom_arc.lock().unwrap().bar.rom_checking();
+      if sha1
+        .as_deref()
+        .is_none_or(|sha1| http::verify_sha1(&dest, sha1).is_ok())
+      {
+        continue;
       }
+      rom_arc.lock().unwrap().bar.rom_redownloading();
+    } else {
+      rom_arc.lock().unwrap().bar.rom_downloading();
     }
-  } else {
-    rom_arc.lock().unwrap().bar.rom_downloading();
-    dl1
-      .fetch(&dest1, DownloadMethod::Https)
-      .map_err(|e| e.to_string())?;
-    dl1.verify_sha1(&dest1).map_err(|e| e.to_string())?;
-    rom_arc.lock().unwrap().bar.rom_done();
+    http::download(urls, &dest, sha1.as_deref()).map_err(|e| e.to_string())?;
+    fetched = true;
   }
-
-  // ── Extra discs (disc 2, 3, …) ────────────────────────────────────────
-  for (ia_path, local_name) in &extra_discs {
-    let dest = directory.join(local_name);
-    let dl = Download::new(&metadata, ia_path).map_err(|e| e.to_string())?;
-    if dest.exists() && dl.verify_sha1(&dest).is_ok() {
-      continue; // already valid
-    }
-    dl.fetch(&dest, DownloadMethod::Https)
-      .map_err(|e| e.to_string())?;
-    dl.verify_sha1(&dest).map_err(|e| e.to_string())?;
+  if fetched {
+    rom_arc.lock().unwrap().bar.rom_done();
+  } else {
+    rom_arc.lock().unwrap().bar.rom_skipped();
   }
 
   Ok(StepStatus::Done)
@@ -198,6 +217,10 @@ pub(crate) fn handle_download_rom(
 /// Iterates over the 8 canonical media types in order. Already-valid files
 /// are skipped (sha1 verified). Updates the bar icons for each type.
 ///
+/// Once downloads are done, media files left over from a previous run (kind
+/// dropped by ScreenScraper or format changed) are removed so the package
+/// templates' globs don't install them.
+///
 /// Takes `rom.medias` out temporarily to avoid holding the Rom lock during
 /// downloads, then puts it back on completion.
 pub(crate) fn handle_download_medias(
@@ -205,11 +228,19 @@ pub(crate) fn handle_download_medias(
   _step_idx: usize,
   ctx: &WorkerContext,
 ) -> Result<StepStatus, String> {
-  let (filename, medias) = {
+  let (filename, medias, rom_files) = {
     let mut rom = rom_arc.lock().unwrap();
     let filename = rom.source.filename.clone();
     let medias = rom.medias.take(); // temporarily take ownership
-    (filename, medias)
+    let mut rom_files: Vec<String> = vec![rom.source.disc1_filename()];
+    rom_files.extend(
+      rom
+        .source
+        .extra_discs
+        .iter()
+        .map(|d| package_file(&d.filename)),
+    );
+    (filename, medias, rom_files)
   };
 
   let directory = Path::new(&filename).with_extension("");
@@ -247,6 +278,19 @@ pub(crate) fn handle_download_medias(
         }
       }
     }
+
+    // ── Reconcile: drop media files that no longer belong ─────────────────
+    for stale in stale_media_files(&directory, medias, &rom_files) {
+      let line = match fs::remove_file(&stale) {
+        Ok(()) => format!("[DownloadMedias] removed stale {}", stale.display()),
+        Err(e) => format!(
+          "[DownloadMedias] could not remove stale {}: {}",
+          stale.display(),
+          e
+        ),
+      };
+      rom_arc.lock().unwrap().debug_log.push(line);
+    }
   }
 
   // Restore medias so SaveState can record their sha1s.
@@ -4,8 +4,9 @@ use std::{
 };
 
 use crate::{
-  package::{read_pkgver, Package},
-  rom::{Rom, RomSource, StepStatus},
+  folder::mirror_url,
+  package::{has_install_script, read_pkgver, Package},
+  rom::{HttpSource, IaSource, Rom, RomSource, StepStatus},
 };
 
 use super::super::{helpers::check_media_changes, WorkerContext};
@@ -22,12 +23,24 @@ pub(crate) fn handle_build_package(
   ctx: &WorkerContext,
 ) -> Result<StepStatus, String> {
   // Extract what we need, releasing the lock before expensive I/O.
-  let (filename, disc1_filename, sha1, rom_url, extra_discs_info, jeu, rom_unchanged) = {
+  let (
+    filename,
+    romname,
+    disc1_filename,
+    sha1,
+    hashes,
+    rom_url,
+    extra_discs_info,
+    jeu,
+    rom_unchanged,
+  ) = {
     let mut rom = rom_arc.lock().unwrap();
-    let jeu = rom.jeu.take(); // Package::new takes ownership; we'll put it back
     let sha1 = rom.sha1.clone().unwrap_or_default();
+    // Actual disc-1 filename (differs from virtual filename for multi-disc games).
+    let disc1_filename = rom.source.disc1_filename();
     let (rom_url, extra_discs_info) = match &rom.source.source {
-      RomSource::InternetArchive(ia) => {
+      RomSource::InternetArchive(IaSource { rom_url, .. })
+      | RomSource::Http(HttpSource { rom_url, .. }) => {
         let extras: Vec<(String, String, String)> = rom
           .source
           .extra_discs
@@ -40,34 +53,38 @@ pub(crate) fn handle_build_package(
             )
           })
           .collect();
-        (ia.rom_url.clone(), extras)
+        (rom_url.clone(), extras)
       }
-      RomSource::Folder(_) => {
+      RomSource::Folder(f) => {
+        // Local sources (empty URL) unless a mirror serves the folder; the
+        // extra files' URLs are set at collection.
+        // Extra-disc sha1s come from ComputeHashes, not from the source.
+        if rom.extra_disc_sha1s.len() != rom.source.extra_discs.len() {
+          return Err(format!(
+            "{} extra file(s) but {} sha1(s) computed",
+            rom.source.extra_discs.len(),
+            rom.extra_disc_sha1s.len()
+          ));
+        }
         let extras: Vec<(String, String, String)> = rom
           .source
           .extra_discs
           .iter()
-          .map(|d| {
-            (
-              d.filename.clone(),
-              String::new(),
-              d.sha1.clone().unwrap_or_default(),
-            )
-          })
+          .zip(rom.extra_disc_sha1s.iter())
+          .map(|(d, sha1)| (d.filename.clone(), d.rom_url.clone(), sha1.clone()))
           .collect();
-        (String::new(), extras)
+        (mirror_url(f.mirror.as_deref(), &f.rel_path), extras)
       }
     };
-    // Actual disc-1 filename (differs from virtual filename for multi-disc games).
-    let disc1_filename = Path::new(&rom.source.file_name)
-      .file_name()
-      .and_then(|n| n.to_str())
-      .unwrap_or(&rom.source.filename)
-      .to_string();
+    // Taken after the checks above, and put back on failure below: a retried
+    // step needs it.
+    let jeu = rom.jeu.take(); // Package::new takes ownership; we'll put it back
     (
       rom.source.filename.clone(),
+      rom.source.romname.clone(),
       disc1_filename,
       sha1,
+      (rom.md5.clone(), rom.crc32.clone()),
       rom_url,
       extra_discs_info,
       jeu,
@@ -86,11 +103,25 @@ pub(crate) fn handle_build_package(
     extra_discs_info,
   )
   .map_err(|e| e.to_string())?;
+  package.romname = romname;
+  package.game_dir = rom_arc.lock().unwrap().source.game_dir.clone();
+  let origin = rom_arc.lock().unwrap().source.origin.clone();
+  (package.md5, package.crc32) = hashes;
+  package.replaces = ctx
+    .state
+    .lock()
+    .unwrap()
+    .roms
+    .get(&filename)
+    .map(|e| e.replaces.clone())
+    .unwrap_or_default();
 
   let lang_refs: Vec<&str> = ctx.lang.iter().map(|s| s.as_str()).collect();
 
   // Check if description.xml content would change (pure read, no I/O side effect).
   let description_changed = package.check_description_changed(&ctx.system, &lang_refs);
+  let game_conf_changed = package.check_game_conf_changed(&ctx.system, &lang_refs);
+  let sources_changed = package.check_sources_changed(&ctx.system);
 
   // ── Delta check: skip build if ROM + all media sha1s + description are unchanged ─
   let (package_changed, debug_lines) = {
@@ -100,6 +131,42 @@ pub(crate) fn handle_build_package(
         true,
         vec!["[BuildPackage] no state entry → package_changed: true".to_string()],
       ),
+      Some(prev) if prev.romname.as_ref().is_some_and(|n| *n != package.romname) => (
+        true,
+        vec![format!(
+          "[BuildPackage] romname changed (state={}, current={}) → package_changed: true",
+          prev.romname.as_deref().unwrap_or(""),
+          package.romname
+        )],
+      ),
+      Some(prev) if prev.origin.as_ref().is_some_and(|o| *o != origin) => (
+        true,
+        vec![format!(
+          "[BuildPackage] origin changed (state={}, current={}) → package_changed: true",
+          prev.origin.as_deref().unwrap_or(""),
+          origin
+        )],
+      ),
+      Some(_)
+        if has_install_script(&Path::new(&filename).with_extension(""))
+          .is_some_and(|hooks| hooks != ctx.system.install_hooks) =>
+      {
+        (
+          true,
+          vec![format!(
+            "[BuildPackage] install_hooks changed (now {}) → package_changed: true",
+            ctx.system.install_hooks
+          )],
+        )
+      }
+      Some(_) if sources_changed => (
+        true,
+        vec!["[BuildPackage] ROM or media sources changed → package_changed: true".to_string()],
+      ),
+      Some(_) if game_conf_changed => (
+        true,
+        vec!["[BuildPackage] game_config changed → package_changed: true".to_string()],
+      ),
       Some(prev) => {
         if !rom_unchanged {
           let line = format!(
@@ -133,9 +200,10 @@ pub(crate) fn handle_build_package(
   if package_changed {
     let dir = Path::new(&filename).with_extension("");
     let pkgver = read_pkgver(&dir) + 1;
-    package
-      .build(&ctx.system, &lang_refs, pkgver)
-      .map_err(|e| e.to_string())?;
+    if let Err(e) = package.build(&ctx.system, &lang_refs, pkgver) {
+      rom_arc.lock().unwrap().jeu = package.jeu.take();
+      return Err(e.to_string());
+    }
   }
 
   // Show description.xml icon: green if written/updated, gray if unchanged.
@@ -146,7 +214,7 @@ pub(crate) fn handle_build_package(
   }
 
   // Move results back into rom.
-  let romname = package.normalize_name();
+  let romname = package.romname;
   let medias = package.medias;
   {
     let mut rom = rom_arc.lock().unwrap();
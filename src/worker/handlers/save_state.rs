@@ -47,6 +47,15 @@ pub(crate) fn handle_save_state(
   // Collect ROM data while holding the lock, then release before I/O.
   let (filename, entry, package_unchanged, debug_log) = {
     let rom = rom_arc.lock().unwrap();
+    // Rename history is set at collection time, not by the pipeline.
+    let replaces = ctx
+      .state
+      .lock()
+      .unwrap()
+      .roms
+      .get(&rom.source.filename)
+      .map(|e| e.replaces.clone())
+      .unwrap_or_default();
     let ss_game_id = rom.jeu.as_ref().map(|j| j.id.clone());
     let medias = rom
       .medias
@@ -58,8 +67,23 @@ pub(crate) fn handle_save_state(
       rom_sha1: rom.sha1.clone().unwrap_or_default(),
       rom_mtime: rom.mtime,
       rom_size: rom.size,
+      rom_crc32: rom.crc32.clone(),
+      rom_md5: rom.md5.clone(),
       medias,
       extra_disc_sha1s: rom.extra_disc_sha1s.clone(),
+      romname: Some(rom.source.romname.clone()),
+      replaces,
+      game_dir: rom.source.game_dir.clone(),
+      game_files: match rom.source.game_dir {
+        Some(_) => rom
+          .source
+          .extra_discs
+          .iter()
+          .map(|d| d.filename.clone())
+          .collect(),
+        None => Vec::new(),
+      },
+      origin: Some(rom.source.origin.clone()),
     };
     (
       rom.source.filename.clone(),
@@ -1,9 +1,26 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+};
 
+pub(crate) use crate::package::media_filename;
 use crate::package::Medias;
 
 pub(crate) const NAME_REGIONS: &[&str] = &["wor", "eu", "us", "fr", "jp", "ss"];
 
+/// Canonical media kinds, as used for output filenames and state keys.
+const MEDIA_KINDS: &[&str] = &[
+  "video",
+  "image",
+  "thumbnail",
+  "bezel",
+  "marquee",
+  "screenshot",
+  "wheel",
+  "manual",
+];
+
 /// Strips the file extension and region/revision tags from a ROM filename to
 /// produce a clean title suitable for a ScreenScraper name search.
 ///
@@ -22,13 +39,46 @@ pub(crate) fn search_name(filename: &str) -> String {
     .to_string()
 }
 
-/// Returns the output filename for a downloaded media asset.
-pub(crate) fn media_filename(kind: &str, format: &str) -> String {
-  match kind {
-    "video" => "video.mp4".to_string(),
-    "manual" => "manual.pdf".to_string(),
-    _ => format!("{}.{}", kind, format),
-  }
+/// Lists media files in `directory` that no longer belong to `medias`.
+///
+/// A file is stale when its stem is one of the canonical media kinds but its
+/// name differs from the one expected for that kind — the kind was dropped by
+/// ScreenScraper, or its format changed (e.g. `image.png` → `image.jpg`).
+/// Files named in `keep` (the ROM files themselves) are never reported.
+pub(crate) fn stale_media_files(
+  directory: &Path,
+  medias: &Medias,
+  keep: &[String],
+) -> Vec<PathBuf> {
+  let expected: Vec<String> = [
+    ("video", medias.video.as_ref()),
+    ("image", medias.image.as_ref()),
+    ("thumbnail", medias.thumbnail.as_ref()),
+    ("bezel", medias.bezel.as_ref()),
+    ("marquee", medias.marquee.as_ref()),
+    ("screenshot", medias.screenshot.as_ref()),
+    ("wheel", medias.wheel.as_ref()),
+    ("manual", medias.manual.as_ref()),
+  ]
+  .into_iter()
+  .filter_map(|(kind, media)| media.map(|m| media_filename(kind, &m.format)))
+  .collect();
+
+  let Ok(entries) = fs::read_dir(directory) else {
+    return Vec::new();
+  };
+  entries
+    .flatten()
+    .map(|e| e.path())
+    .filter(|path| path.is_file())
+    .filter(|path| {
+      let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+      let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+      MEDIA_KINDS.contains(&stem)
+        && !expected.iter().any(|e| e == name)
+        && !keep.iter().any(|k| k == name)
+    })
+    .collect()
 }
 
 /// Compares current media sha1s (from SS) against the saved state.